@@ -0,0 +1,29 @@
+// Pass the revision of the rust-code-analysis dependency to the measure cache, so entries
+// written by another revision are not reused.
+
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    let manifest = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.toml");
+    let text = std::fs::read_to_string(manifest).unwrap_or_default();
+    println!("cargo:rustc-env=RCA_REVISION={}", rca_revision(&text));
+}
+
+/// The `rev`, or else the `version`, of the `rust-code-analysis` dependency.
+fn rca_revision(manifest: &str) -> String {
+    let line = manifest
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with("rust-code-analysis") && l.contains('='));
+    let value = |key: &str| -> Option<String> {
+        line?.split(key).skip(1).find_map(|rest| {
+            let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+            Some(rest.strip_prefix('"')?.split('"').next()?.to_string())
+        })
+    };
+    value("rev")
+        .or_else(|| value("version"))
+        .or_else(|| value("rust-code-analysis"))
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -24,5 +24,11 @@ fn main() {
                 panic!("Err in init config. {:?}", e);
             }
         }
+        // Inspect config
+        command::Command::Config(a) => {
+            if let Err(e) = command::config::config(a) {
+                panic!("Err in config. {:?}", e);
+            }
+        }
     }
 }
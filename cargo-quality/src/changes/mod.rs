@@ -0,0 +1,177 @@
+// Files and lines changed since a git ref, for `check --since <ref>`.
+//
+// The changes are the diff from the merge base of the ref and `HEAD` to the working tree,
+// index included, so a branch is compared with the point it forked from. Untracked files
+// count as changed. Findings outside the changes are reported as context and not scored.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// What changed in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChanges {
+    /// An added or untracked file.
+    Whole,
+    /// 1-based inclusive ranges of the new lines. A deletion touches the lines around it.
+    Lines(Vec<(usize, usize)>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Changes {
+    /// The ref given to `--since`.
+    pub since: String,
+    /// The commit the working tree is compared with.
+    pub base: String,
+    /// By canonical path.
+    files: HashMap<PathBuf, FileChanges>,
+}
+
+/// 1-based inclusive range of the new lines of a hunk. A pure deletion has no new line and
+/// git gives the line before it, `0` at the top of the file, so it touches that line and the
+/// next one.
+fn hunk_range(new_start: u32, new_lines: u32) -> (usize, usize) {
+    let start = new_start as usize;
+    match new_lines {
+        0 => (start.max(1), start + 1),
+        n => (start, start + n as usize - 1),
+    }
+}
+
+impl Changes {
+    /// Diff the working tree of the repository holding `dir` against `since`.
+    pub fn since(dir: &Path, since: &str) -> Result<Changes> {
+        let repo = git2::Repository::discover(dir)
+            .with_context(|| format!("No git repository found at {:?}", dir))?;
+        let workdir = repo
+            .workdir()
+            .context("The git repository is bare")?
+            .canonicalize()?;
+        let commit = repo
+            .revparse_single(since)
+            .and_then(|o| o.peel_to_commit())
+            .with_context(|| format!("Unknown git ref {:?}", since))?;
+        let base = match repo.head().and_then(|h| h.peel_to_commit()) {
+            Ok(head) => repo
+                .merge_base(commit.id(), head.id())
+                .and_then(|id| repo.find_commit(id))
+                .unwrap_or(commit),
+            Err(_) => commit,
+        };
+
+        let mut opts = git2::DiffOptions::new();
+        opts.context_lines(0)
+            .include_untracked(true)
+            .recurse_untracked_dirs(true);
+        let diff = repo.diff_tree_to_workdir_with_index(Some(&base.tree()?), Some(&mut opts))?;
+        let mut files = HashMap::new();
+        for (i, delta) in diff.deltas().enumerate() {
+            let path = match delta.new_file().path() {
+                Some(p) => workdir.join(p),
+                None => continue,
+            };
+            let changes = match delta.status() {
+                git2::Delta::Deleted => continue,
+                git2::Delta::Added | git2::Delta::Untracked => FileChanges::Whole,
+                _ => {
+                    let mut lines = vec![];
+                    if let Some(patch) = git2::Patch::from_diff(&diff, i)? {
+                        for h in 0..patch.num_hunks() {
+                            let (hunk, _) = patch.hunk(h)?;
+                            lines.push(hunk_range(hunk.new_start(), hunk.new_lines()));
+                        }
+                    }
+                    FileChanges::Lines(lines)
+                }
+            };
+            files.insert(path, changes);
+        }
+        log::info!(
+            "Changes since {} ({}): {} files",
+            since,
+            base.id(),
+            files.len()
+        );
+        Ok(Changes {
+            since: since.to_string(),
+            base: base.id().to_string(),
+            files,
+        })
+    }
+
+    /// Number of the changed files, deleted ones left out.
+    pub fn file_count(&self) -> u64 {
+        self.files.len() as u64
+    }
+
+    fn get(&self, file: &Path) -> Option<&FileChanges> {
+        match file.canonicalize() {
+            Ok(p) => self.files.get(&p),
+            Err(_) => self.files.get(file),
+        }
+    }
+
+    /// Whether the file changed.
+    pub fn touches_file(&self, file: &Path) -> bool {
+        self.get(file).is_some()
+    }
+
+    /// Whether a changed line is in the 1-based inclusive range of the file.
+    pub fn touches(&self, file: &Path, start: usize, end: usize) -> bool {
+        match self.get(file) {
+            Some(FileChanges::Whole) => true,
+            Some(FileChanges::Lines(lines)) => lines.iter().any(|(s, e)| *s <= end && start <= *e),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(file: &str, changes: FileChanges) -> Changes {
+        Changes {
+            since: "main".to_string(),
+            base: String::new(),
+            files: HashMap::from([(PathBuf::from(file), changes)]),
+        }
+    }
+
+    #[test]
+    fn hunk_of_new_lines() {
+        assert_eq!(hunk_range(10, 1), (10, 10));
+        assert_eq!(hunk_range(10, 3), (10, 12));
+    }
+
+    #[test]
+    fn hunk_of_deletion() {
+        assert_eq!(hunk_range(10, 0), (10, 11));
+        assert_eq!(hunk_range(0, 0), (1, 1));
+    }
+
+    #[test]
+    fn touches_overlapping_range() {
+        let c = changes("/no/such/a.rs", FileChanges::Lines(vec![(10, 12)]));
+        let file = Path::new("/no/such/a.rs");
+        assert!(c.touches(file, 1, 10));
+        assert!(c.touches(file, 11, 11));
+        assert!(c.touches(file, 12, 20));
+        assert!(!c.touches(file, 1, 9));
+        assert!(!c.touches(file, 13, 20));
+    }
+
+    #[test]
+    fn touches_whole_file() {
+        let c = changes("/no/such/a.rs", FileChanges::Whole);
+        assert!(c.touches(Path::new("/no/such/a.rs"), 100, 200));
+        assert!(c.touches_file(Path::new("/no/such/a.rs")));
+    }
+
+    #[test]
+    fn untouched_file() {
+        let c = changes("/no/such/a.rs", FileChanges::Lines(vec![(1, 5)]));
+        assert!(!c.touches(Path::new("/no/such/b.rs"), 1, 5));
+        assert!(!c.touches_file(Path::new("/no/such/b.rs")));
+    }
+}
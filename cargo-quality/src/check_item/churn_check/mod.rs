@@ -0,0 +1,40 @@
+use crate::config;
+use crate::context;
+use crate::result;
+use crate::toolchains;
+use anyhow::{Context, Result};
+use log;
+use std::sync::Arc;
+
+/// Churn check struct
+#[derive(Debug)]
+pub struct ChurnCheck {
+    pub project: config::ProjectInfoConfig,
+    pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
+}
+
+impl super::CheckItem for ChurnCheck {
+    /// Perform the churn check
+    /// The result is written to file
+    fn check(&mut self, result: &mut result::Result) -> Result<()> {
+        log::info!("Churn check: {:?}", self.project);
+        std::fs::create_dir_all(&self.project.result)
+            .with_context(|| format!("Create result dir path failed! {:?}", self.project))?;
+        let mut churn_check_tool = toolchains::make_check(
+            self.config.clone(),
+            toolchains::CheckTool::GitForChurn,
+            &self.project,
+            self.context.clone(),
+        );
+        churn_check_tool.check()?;
+        churn_check_tool.parse()?;
+        churn_check_tool.count()?;
+        churn_check_tool.result(result)?;
+        Ok(())
+    }
+
+    fn item(&self) -> config::QualityEvaluation {
+        config::QualityEvaluation::Churn
+    }
+}
@@ -0,0 +1,40 @@
+use crate::config;
+use crate::context;
+use crate::result;
+use crate::toolchains;
+use anyhow::{Context, Result};
+use log;
+use std::sync::Arc;
+
+/// Doc check struct
+#[derive(Debug)]
+pub struct DocCheck {
+    pub project: config::ProjectInfoConfig,
+    pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
+}
+
+impl super::CheckItem for DocCheck {
+    /// Perform the doc check
+    /// The result is written to file
+    fn check(&mut self, result: &mut result::Result) -> Result<()> {
+        log::info!("Doc check: {:?}", self.project);
+        std::fs::create_dir_all(&self.project.result)
+            .with_context(|| format!("Create result dir path failed! {:?}", self.project))?;
+        let mut doc_check_tool = toolchains::make_check(
+            self.config.clone(),
+            toolchains::CheckTool::SynForDocCoverage,
+            &self.project,
+            self.context.clone(),
+        );
+        doc_check_tool.check()?;
+        doc_check_tool.parse()?;
+        doc_check_tool.count()?;
+        doc_check_tool.result(result)?;
+        Ok(())
+    }
+
+    fn item(&self) -> config::QualityEvaluation {
+        config::QualityEvaluation::Doc
+    }
+}
@@ -0,0 +1,40 @@
+use crate::config;
+use crate::context;
+use crate::result;
+use crate::toolchains;
+use anyhow::{Context, Result};
+use log;
+use std::sync::Arc;
+
+/// Duplication check struct
+#[derive(Debug)]
+pub struct DuplicationCheck {
+    pub project: config::ProjectInfoConfig,
+    pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
+}
+
+impl super::CheckItem for DuplicationCheck {
+    /// Perform the duplication check
+    /// The result is written to file
+    fn check(&mut self, result: &mut result::Result) -> Result<()> {
+        log::info!("Duplication check: {:?}", self.project);
+        std::fs::create_dir_all(&self.project.result)
+            .with_context(|| format!("Create result dir path failed! {:?}", self.project))?;
+        let mut duplication_check_tool = toolchains::make_check(
+            self.config.clone(),
+            toolchains::CheckTool::TokensForDuplication,
+            &self.project,
+            self.context.clone(),
+        );
+        duplication_check_tool.check()?;
+        duplication_check_tool.parse()?;
+        duplication_check_tool.count()?;
+        duplication_check_tool.result(result)?;
+        Ok(())
+    }
+
+    fn item(&self) -> config::QualityEvaluation {
+        config::QualityEvaluation::Duplication
+    }
+}
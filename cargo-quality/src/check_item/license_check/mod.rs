@@ -1,8 +1,10 @@
 use crate::config;
+use crate::context;
 use crate::result;
 use crate::toolchains;
 use anyhow::{Context, Result};
 use log;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct LicenseCheckResult {
@@ -29,6 +31,7 @@ impl result::ResultInfo for LicenseCheckResult {
 pub struct LicenseCheck {
     pub project: config::ProjectInfoConfig,
     pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
 }
 
 impl super::CheckItem for LicenseCheck {
@@ -42,6 +45,7 @@ impl super::CheckItem for LicenseCheck {
             self.config.clone(),
             toolchains::CheckTool::CargoDenyForLicenseCheck,
             &self.project,
+            self.context.clone(),
         );
         measure_check_tool.check()?;
         measure_check_tool.parse()?;
@@ -49,4 +53,8 @@ impl super::CheckItem for LicenseCheck {
         measure_check_tool.result(result)?;
         Ok(())
     }
+
+    fn item(&self) -> config::QualityEvaluation {
+        config::QualityEvaluation::License
+    }
 }
@@ -1,8 +1,10 @@
 use crate::config;
+use crate::context;
 use crate::result;
 use crate::toolchains;
 use anyhow::{Context, Result};
 use log;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct MeasureCheckResult {
@@ -29,6 +31,7 @@ impl result::ResultInfo for MeasureCheckResult {
 pub struct MeasureCheck {
     pub project: config::ProjectInfoConfig,
     pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
 }
 
 impl super::CheckItem for MeasureCheck {
@@ -42,6 +45,7 @@ impl super::CheckItem for MeasureCheck {
             self.config.clone(),
             toolchains::CheckTool::RustCodeAnalysisForMeasure,
             &self.project,
+            self.context.clone(),
         );
         measure_check_tool.check()?;
         measure_check_tool.parse()?;
@@ -49,4 +53,8 @@ impl super::CheckItem for MeasureCheck {
         measure_check_tool.result(result)?;
         Ok(())
     }
+
+    fn item(&self) -> config::QualityEvaluation {
+        config::QualityEvaluation::Measure
+    }
 }
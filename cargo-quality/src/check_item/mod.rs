@@ -1,14 +1,20 @@
 use crate::config;
+use crate::context;
 use crate::result;
 use anyhow::Result;
 use std::path::PathBuf;
+use std::sync::Arc;
+pub mod churn_check;
+pub mod doc_check;
+pub mod duplication_check;
 pub mod license_check;
 pub mod measure_check;
 pub mod static_check;
 
 /// Trait for check item
-pub trait CheckItem: std::fmt::Debug {
+pub trait CheckItem: std::fmt::Debug + Send {
     fn check(&mut self, result: &mut result::Result) -> Result<()>;
+    fn item(&self) -> config::QualityEvaluation;
 }
 
 /// Create check item
@@ -16,24 +22,44 @@ pub fn make_check<'b>(
     c: config::Config,
     q: &'b config::QualityEvaluation,
     p: &'b config::ProjectInfoConfig,
+    ctx: Arc<context::ProjectContext>,
 ) -> Box<dyn CheckItem> {
     let project_cfg = config::ProjectInfoConfig {
         dir: p.dir.clone(),
         manifest: p.manifest.clone(),
+        package: p.package.clone(),
         result: make_result_path(q, &p.result),
     };
     match &q {
         config::QualityEvaluation::StaticCheck => Box::new(static_check::StaticCheck {
             project: project_cfg,
             config: c,
+            context: ctx,
         }),
         config::QualityEvaluation::License => Box::new(license_check::LicenseCheck {
             project: project_cfg,
             config: c,
+            context: ctx,
         }),
         config::QualityEvaluation::Measure => Box::new(measure_check::MeasureCheck {
             project: project_cfg,
             config: c,
+            context: ctx,
+        }),
+        config::QualityEvaluation::Churn => Box::new(churn_check::ChurnCheck {
+            project: project_cfg,
+            config: c,
+            context: ctx,
+        }),
+        config::QualityEvaluation::Duplication => Box::new(duplication_check::DuplicationCheck {
+            project: project_cfg,
+            config: c,
+            context: ctx,
+        }),
+        config::QualityEvaluation::Doc => Box::new(doc_check::DocCheck {
+            project: project_cfg,
+            config: c,
+            context: ctx,
         }),
     }
 }
@@ -41,12 +67,15 @@ pub fn make_check<'b>(
 /// Store the check item results
 /// The purpose of this file is to make it easy for users to view detailed problems and fix them
 #[allow(clippy::ptr_arg)]
-fn make_result_path(c: &config::QualityEvaluation, dir: &PathBuf) -> PathBuf {
+pub fn make_result_path(c: &config::QualityEvaluation, dir: &PathBuf) -> PathBuf {
     let mut result_path = dir.clone();
     match c {
         config::QualityEvaluation::StaticCheck => result_path.push(r"static_check"),
         config::QualityEvaluation::License => result_path.push(r"license_check"),
         config::QualityEvaluation::Measure => result_path.push(r"measure_check"),
+        config::QualityEvaluation::Churn => result_path.push(r"churn_check"),
+        config::QualityEvaluation::Duplication => result_path.push(r"duplication_check"),
+        config::QualityEvaluation::Doc => result_path.push(r"doc_check"),
     }
     result_path
 }
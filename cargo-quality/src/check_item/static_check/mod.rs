@@ -1,8 +1,10 @@
 use crate::config;
+use crate::context;
 use crate::result;
 use crate::toolchains;
 use anyhow::{Context, Result};
 use log;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct StaticCheckResult {
@@ -29,6 +31,7 @@ impl result::ResultInfo for StaticCheckResult {
 pub struct StaticCheck {
     pub project: config::ProjectInfoConfig,
     pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
 }
 
 impl super::CheckItem for StaticCheck {
@@ -42,6 +45,7 @@ impl super::CheckItem for StaticCheck {
             self.config.clone(),
             toolchains::CheckTool::ClippyForStaticCheck,
             &self.project,
+            self.context.clone(),
         );
         static_check_tool.check()?;
         static_check_tool.parse()?;
@@ -49,4 +53,8 @@ impl super::CheckItem for StaticCheck {
         static_check_tool.result(result)?;
         Ok(())
     }
+
+    fn item(&self) -> config::QualityEvaluation {
+        config::QualityEvaluation::StaticCheck
+    }
 }
@@ -1,9 +1,10 @@
 use crate::config;
 use crate::project;
+use crate::util;
 
 use log;
 
-use anyhow::Error;
+use anyhow::{Context, Error};
 use std::path::PathBuf;
 
 #[derive(clap::ValueEnum, Debug, PartialEq, Eq, Copy, Clone)]
@@ -19,6 +20,20 @@ pub struct Args {
     pub config: Option<PathBuf>,
     #[clap(short, long, action)]
     pub project: Option<PathBuf>,
+    /// Workspace member to evaluate, may be repeated. All members by default.
+    #[clap(long = "package", action)]
+    pub packages: Vec<String>,
+    /// Override a config value, e.g. `quality_evaluation_cfg.static_check_cfg.warn_score=5`.
+    /// Takes precedence over the `CARGO_QUALITY_*` environment variables and the config file.
+    #[clap(long, action)]
+    pub set: Vec<String>,
+    /// Directory of the report. `<project>/cargo_quality_evaluation` by default.
+    #[clap(long, action)]
+    pub out_dir: Option<PathBuf>,
+    /// Only score the findings in the files and lines changed since this git ref, e.g. `main`.
+    /// The other findings are reported as context.
+    #[clap(long, action)]
+    pub since: Option<String>,
     /// The check to perform
     #[clap(value_enum, action, default_value_t = CheckItem::All)]
     pub check_item: CheckItem,
@@ -28,18 +43,6 @@ pub struct Args {
 pub fn check(args: Args) -> Result<(), Error> {
     let now_path = std::env::current_dir().unwrap();
 
-    let cfg_path = match args.config {
-        Some(p) => p,
-        _ => {
-            let mut p = PathBuf::new();
-            p.push(now_path.clone());
-            p.push(r"quality-evaluation.toml");
-            p
-        }
-    };
-    let cfg = config::parse(cfg_path)?;
-    log::info!("config : {:?}", cfg);
-
     let project_path = match args.project {
         Some(p) => {
             log::info!("project_path from arg: {:?}", p);
@@ -51,11 +54,19 @@ pub fn check(args: Args) -> Result<(), Error> {
         }
     };
 
-    if let Ok(mut p) = project::Project::new(project_path, &cfg) {
-        p.execute()?;
-        let r = p.get_result();
-        log::info!("result json: {:?}", serde_json::json!(r));
+    let (cfg, cfg_source) = config::discover(args.config, &project_path, &args.set)?;
+    log::info!("config from {:?}: {:?}", cfg_source, cfg);
+
+    let mut p = project::Project::new(project_path, &cfg, &args.packages, args.out_dir, args.since)
+        .context("Init project failed")?;
+    p.result.config_source = Some(cfg_source);
+    // Ctrl-C stops the evaluation gracefully, elsewhere it keeps its default behavior.
+    if let Err(e) = util::install_cancel_handler() {
+        log::warn!("{:?}", e);
     }
+    p.execute()?;
+    let r = p.get_result();
+    log::info!("result json: {:?}", serde_json::json!(r));
 
     Ok(())
 }
@@ -0,0 +1,111 @@
+use anyhow::Error;
+use std::path::PathBuf;
+
+#[derive(clap::Parser, Debug)]
+pub enum Args {
+    /// Print the effective config, after `extends` and the overrides are applied.
+    #[clap(name = "dump")]
+    Dump(DumpArgs),
+    /// Report unknown keys, missing values, wrong weights and out of range thresholds.
+    #[clap(name = "validate")]
+    Validate(DumpArgs),
+    /// Print the JSON Schema of quality-evaluation.toml, for editor completion.
+    #[clap(name = "schema")]
+    Schema,
+    /// Upgrade the config to the current version in place, keeping its comments.
+    #[clap(name = "migrate")]
+    Migrate(MigrateArgs),
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct DumpArgs {
+    #[clap(short, long, action)]
+    pub config: Option<PathBuf>,
+    #[clap(short, long, action)]
+    pub project: Option<PathBuf>,
+    /// Override a config value, e.g. `quality_evaluation_cfg.static_check_cfg.warn_score=5`.
+    #[clap(long, action)]
+    pub set: Vec<String>,
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct MigrateArgs {
+    #[clap(short, long, action)]
+    pub config: Option<PathBuf>,
+    #[clap(short, long, action)]
+    pub project: Option<PathBuf>,
+    /// Print the migrated config instead of writing it.
+    #[clap(long, action)]
+    pub dry_run: bool,
+}
+
+/// Subcommand config
+pub fn config(args: Args) -> Result<(), Error> {
+    match args {
+        Args::Dump(a) => dump(a),
+        Args::Validate(a) => validate(a),
+        Args::Migrate(a) => migrate(a),
+        Args::Schema => {
+            let schema = crate::config::validate::schema();
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
+    }
+}
+
+fn validate(args: DumpArgs) -> Result<(), Error> {
+    let project_path = args
+        .project
+        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    let diagnostics = crate::config::validate::validate(args.config, &project_path, &args.set)?;
+    for d in diagnostics.iter() {
+        println!("{}", d);
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == crate::config::validate::Severity::Error)
+        .count();
+    anyhow::ensure!(errors == 0, "config has {} error(s)", errors);
+    println!("config is valid");
+    Ok(())
+}
+
+fn dump(args: DumpArgs) -> Result<(), Error> {
+    let project_path = args
+        .project
+        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    let (value, source) = crate::config::discover_value(args.config, &project_path, &args.set)?;
+    println!("# Loaded from {:?}", source);
+    println!("{}", toml::to_string_pretty(&value)?);
+    Ok(())
+}
+
+fn migrate(args: MigrateArgs) -> Result<(), Error> {
+    use crate::config::{migrate, ConfigSource};
+
+    let project_path = args
+        .project
+        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    let (_, source) = crate::config::discover_value(args.config, &project_path, &[])?;
+    let (path, prefix): (_, &[&str]) = match &source {
+        ConfigSource::Arg(p) | ConfigSource::File(p) => (p, &[]),
+        ConfigSource::PackageMetadata(p) => (p, &["package", "metadata", "quality"]),
+        ConfigSource::WorkspaceMetadata(p) => (p, &["workspace", "metadata", "quality"]),
+    };
+    let version = migrate::migrate_file(path, prefix, args.dry_run)?;
+    if args.dry_run {
+        return Ok(());
+    }
+    if version < migrate::CURRENT_VERSION {
+        println!(
+            "Migrated {:?} from version {} to {}",
+            path,
+            version,
+            migrate::CURRENT_VERSION
+        );
+    } else {
+        println!("{:?} is already at version {}", path, version);
+    }
+    log::info!("Configs in `extends` are migrated on load, run `config migrate --config <path>` to upgrade them in place");
+    Ok(())
+}
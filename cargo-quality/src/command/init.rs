@@ -1,5 +1,6 @@
 use anyhow::{ensure, Context, Error};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use toml_edit::{Document, Item};
 
 #[derive(clap::Parser, Debug)]
 pub struct Args {
@@ -7,9 +8,124 @@ pub struct Args {
     pub config: Option<PathBuf>,
     #[clap(short, long, action)]
     pub project: Option<PathBuf>,
+    /// Thresholds and weights tuned for a kind of project.
+    #[clap(long, value_enum, default_value = "default")]
+    pub preset: Preset,
+    /// Add the keys and check items missing in the existing config, keeping its values.
+    #[clap(long, action)]
+    pub upgrade: bool,
 }
 
-const CONFIG: &[u8] = include_bytes!("../../quality-evaluation-template.toml");
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Preset {
+    /// Low thresholds and high penalties, for code held to a high bar.
+    Strict,
+    /// The thresholds of the template.
+    Default,
+    /// High thresholds, for legacy code or a first evaluation.
+    Lenient,
+    /// Libraries, where license and public API quality weigh more.
+    Library,
+    /// Applications, where license weighs less than the code itself.
+    Application,
+}
+
+impl Preset {
+    /// Values of the preset replacing the ones of the template, by dotted key path.
+    fn values(&self) -> &'static [(&'static str, i64)] {
+        match self {
+            Preset::Default => &[],
+            Preset::Strict => &[
+                ("quality_evaluation_cfg.static_check_cfg.error_score", 100),
+                ("quality_evaluation_cfg.static_check_cfg.warn_score", 20),
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_cyclomatic_complexity",
+                    8,
+                ),
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_cognitive_complexity",
+                    8,
+                ),
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_num_rows_function",
+                    50,
+                ),
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_num_rows_file",
+                    400,
+                ),
+            ],
+            Preset::Lenient => &[
+                ("quality_evaluation_cfg.static_check_cfg.error_score", 30),
+                ("quality_evaluation_cfg.static_check_cfg.warn_score", 5),
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_cyclomatic_complexity",
+                    15,
+                ),
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_cognitive_complexity",
+                    20,
+                ),
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_num_rows_function",
+                    150,
+                ),
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_num_rows_file",
+                    1000,
+                ),
+            ],
+            Preset::Library => &[
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_num_rows_function",
+                    80,
+                ),
+                ("quality_evaluation_cfg.license_cfg.deny_license_score", 50),
+                (
+                    "quality_evaluation_cfg.static_check_cfg.static_check_weight",
+                    35,
+                ),
+                ("quality_evaluation_cfg.measure_cfg.measure_weight", 30),
+                ("quality_evaluation_cfg.license_cfg.license_eval_weight", 35),
+            ],
+            Preset::Application => &[
+                (
+                    "quality_evaluation_cfg.measure_cfg.large_num_rows_function",
+                    120,
+                ),
+                (
+                    "quality_evaluation_cfg.static_check_cfg.static_check_weight",
+                    40,
+                ),
+                ("quality_evaluation_cfg.measure_cfg.measure_weight", 45),
+                ("quality_evaluation_cfg.license_cfg.license_eval_weight", 15),
+            ],
+        }
+    }
+}
+
+const CONFIG: &str = include_str!("../../quality-evaluation-template.toml");
+
+/// The template with the values of the preset, keeping its comments.
+fn preset_config(preset: Preset) -> Result<Document, Error> {
+    let mut doc: Document = CONFIG.parse().context("invalid config template")?;
+    for (path, value) in preset.values() {
+        let keys: Vec<&str> = path.split('.').collect();
+        let mut item = doc.as_item_mut();
+        for k in keys.iter() {
+            item = item
+                .get_mut(k)
+                .with_context(|| format!("preset key {} is not in the template", path))?;
+        }
+        // Keep the comment and spacing around the value.
+        let decor = item.as_value().map(|v| v.decor().clone());
+        *item = toml_edit::value(*value);
+        if let (Some(d), Some(v)) = (decor, item.as_value_mut()) {
+            *v.decor_mut() = d;
+        }
+    }
+    Ok(doc)
+}
 
 /// Subcommand init config
 #[allow(clippy::redundant_clone)]
@@ -22,9 +138,13 @@ pub fn init_config(args: Args) -> Result<(), Error> {
         PathBuf::from("quality-evaluation.toml")
     };
 
+    if args.upgrade {
+        return upgrade_config(&cfg_path, args.preset);
+    }
+
     ensure!(
         std::fs::metadata(&cfg_path).is_err(),
-        "Unable to init config: '{}' already exists",
+        "Unable to init config: '{}' already exists, use --upgrade to add the missing keys",
         cfg_path.display()
     );
 
@@ -34,7 +154,111 @@ pub fn init_config(args: Args) -> Result<(), Error> {
         cfg_path.display()
     );
 
-    std::fs::write(&cfg_path, CONFIG).context("unable to write config file")?;
+    let doc = preset_config(args.preset)?;
+    std::fs::write(&cfg_path, doc.to_string()).context("unable to write config file")?;
 
     Ok(())
 }
+
+/// Add the keys of the preset missing in the existing config, which is migrated first.
+/// A check item is added only when its `<item>_cfg` table is missing too, so an item the
+/// user removed on purpose stays disabled.
+fn upgrade_config(cfg_path: &Path, preset: Preset) -> Result<(), Error> {
+    let text = std::fs::read_to_string(cfg_path)
+        .with_context(|| format!("Unable to upgrade config: failed to read {:?}", cfg_path))?;
+    let mut doc: Document = text
+        .parse()
+        .with_context(|| format!("Unable to upgrade config: failed to parse {:?}", cfg_path))?;
+    crate::config::migrate::migrate_document(&mut doc, &[])?;
+    let template = preset_config(preset)?;
+
+    let mut added = vec![];
+    let new_items: Vec<toml_edit::Value> = template
+        .get("check_quality_item")
+        .and_then(Item::as_array)
+        .map(|a| {
+            a.iter()
+                .filter(|i| match item_name(i) {
+                    Some(n) => {
+                        doc.get("quality_evaluation_cfg")
+                            .and_then(|q| q.get(&item_cfg_table(n)))
+                            .is_none()
+                            && !doc
+                                .get("check_quality_item")
+                                .and_then(Item::as_array)
+                                .map(|a| a.iter().any(|u| item_name(u) == Some(n)))
+                                .unwrap_or(false)
+                    }
+                    None => false,
+                })
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(a) = doc
+        .get_mut("check_quality_item")
+        .and_then(Item::as_array_mut)
+    {
+        for i in new_items {
+            added.push(format!("check_quality_item: {}", i.to_string().trim()));
+            a.push_formatted(i);
+        }
+    }
+
+    add_missing(
+        doc.as_table_mut(),
+        template.as_table(),
+        &mut vec![],
+        &mut added,
+    );
+    std::fs::write(cfg_path, doc.to_string()).context("unable to write config file")?;
+    if added.is_empty() {
+        println!("{:?} is up to date", cfg_path);
+    }
+    for a in added {
+        println!("Added {}", a);
+    }
+    Ok(())
+}
+
+/// Recursively copy the keys of `template` missing in `table`.
+fn add_missing(
+    table: &mut toml_edit::Table,
+    template: &toml_edit::Table,
+    path: &mut Vec<String>,
+    added: &mut Vec<String>,
+) {
+    for (k, v) in template.iter() {
+        path.push(k.to_string());
+        match (table.get_mut(k), v) {
+            (Some(Item::Table(t)), Item::Table(tv)) => add_missing(t, tv, path, added),
+            (Some(_), _) => {}
+            (None, v) => {
+                table.insert(k, v.clone());
+                added.push(path.join("."));
+            }
+        }
+        path.pop();
+    }
+}
+
+/// Name of a check item as written in the config, e.g. `StaticCheck` of `{StaticCheck = {}}`.
+fn item_name(v: &toml_edit::Value) -> Option<&str> {
+    match v {
+        toml_edit::Value::InlineTable(t) => t.iter().next().map(|(k, _)| k),
+        toml_edit::Value::String(s) => Some(s.value().as_str()),
+        _ => None,
+    }
+}
+
+/// Config table of a check item, e.g. `static_check_cfg` for `StaticCheck`.
+fn item_cfg_table(name: &str) -> String {
+    let mut s = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            s.push('_');
+        }
+        s.push(c.to_ascii_lowercase());
+    }
+    s + "_cfg"
+}
@@ -1,4 +1,5 @@
 pub mod check;
+pub mod config;
 pub mod init;
 
 use clap::Parser;
@@ -11,4 +12,7 @@ pub enum Command {
     /// Init config file: quality-evaluation.toml for project.
     #[clap(name = "init")]
     Init(init::Args),
+    /// Inspect the config of the project.
+    #[clap(name = "config", subcommand)]
+    Config(config::Args),
 }
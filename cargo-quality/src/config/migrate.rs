@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use toml_edit::Document;
+
+/// Version of the config format written by this release.
+/// A config without `version` is version 1.
+pub const CURRENT_VERSION: i64 = 2;
+
+pub const VERSION_KEY: &str = "version";
+
+/// Keys renamed by each version: (version, path of the table, old key, new key).
+const RENAMES: &[(i64, &[&str], &str, &str)] = &[(
+    2,
+    &["quality_evaluation_cfg"],
+    "measeure_cfg",
+    "measure_cfg",
+)];
+
+/// Version of the config, 1 when it has none.
+pub fn version_of(value: &toml::Value) -> Result<i64> {
+    match value.get(VERSION_KEY) {
+        None => Ok(1),
+        Some(v) => v
+            .as_integer()
+            .with_context(|| format!("`version` must be an integer, got {}", v)),
+    }
+}
+
+/// Upgrade a parsed config of an older version to the current one, in place.
+pub fn migrate_value(value: &mut toml::Value, path: &Path) -> Result<()> {
+    let version = version_of(value)?;
+    anyhow::ensure!(
+        version <= CURRENT_VERSION,
+        "Config {:?} has version {}, newer than the supported {}, please upgrade cargo-quality",
+        path,
+        version,
+        CURRENT_VERSION
+    );
+    for (v, table, old, new) in RENAMES {
+        if version >= *v {
+            continue;
+        }
+        let mut t = Some(&mut *value);
+        for k in table.iter() {
+            t = t.and_then(|t| t.get_mut(*k));
+        }
+        if let Some(toml::Value::Table(t)) = t {
+            if let Some(item) = t.remove(*old) {
+                t.insert(new.to_string(), item);
+            }
+        }
+    }
+    if let Some(t) = value.as_table_mut() {
+        t.insert(
+            VERSION_KEY.to_string(),
+            toml::Value::Integer(CURRENT_VERSION),
+        );
+    }
+    Ok(())
+}
+
+/// Rewrite the config at `prefix` of the document to the current version, keeping the
+/// comments and formatting. Returns the version it was migrated from.
+pub fn migrate_document(doc: &mut Document, prefix: &[&str]) -> Result<i64> {
+    let mut root = doc.as_table_mut() as &mut dyn toml_edit::TableLike;
+    for k in prefix {
+        root = root
+            .get_mut(k)
+            .and_then(|i| i.as_table_like_mut())
+            .with_context(|| format!("No [{}] table in the manifest", prefix.join(".")))?;
+    }
+    let version = match root.get(VERSION_KEY) {
+        None => 1,
+        Some(v) => v
+            .as_integer()
+            .context("`version` of the config must be an integer")?,
+    };
+    anyhow::ensure!(
+        version <= CURRENT_VERSION,
+        "Config has version {}, newer than the supported {}",
+        version,
+        CURRENT_VERSION
+    );
+
+    for (v, table, old, new) in RENAMES {
+        if version >= *v {
+            continue;
+        }
+        let mut t: Option<&mut dyn toml_edit::TableLike> = Some(&mut *root);
+        for k in table.iter() {
+            t = t
+                .and_then(|t| t.get_mut(k))
+                .and_then(|i| i.as_table_like_mut());
+        }
+        if let Some(t) = t {
+            if let Some(item) = t.remove(old) {
+                t.insert(new, item);
+            }
+        }
+    }
+    root.insert(VERSION_KEY, toml_edit::value(CURRENT_VERSION));
+    Ok(version)
+}
+
+/// Migrate the config file, or the config table in a manifest, in place.
+pub fn migrate_file(path: &Path, prefix: &[&str], dry_run: bool) -> Result<i64> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file from {:?}", path))?;
+    let mut doc: Document = text
+        .parse()
+        .with_context(|| format!("Failed to parse {:?}", path))?;
+    let version = migrate_document(&mut doc, prefix)?;
+    if dry_run {
+        print!("{}", doc);
+    } else if version < CURRENT_VERSION {
+        std::fs::write(path, doc.to_string())
+            .with_context(|| format!("Failed to write {:?}", path))?;
+    }
+    Ok(version)
+}
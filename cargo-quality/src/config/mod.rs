@@ -1,9 +1,14 @@
 use anyhow::{Context, Error, Result};
-use serde_derive::Deserialize;
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
 use std::default::Default;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Copy, Clone, Deserialize)]
+pub mod migrate;
+pub mod validate;
+
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Copy, Clone, Deserialize, JsonSchema)]
 pub enum CommunityEcology {
     DeveloperCount,
 }
@@ -27,14 +32,22 @@ impl std::str::FromStr for CommunityEcology {
     }
 }
 
-#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Copy, Clone, Deserialize)]
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Copy, Clone, Deserialize, JsonSchema)]
 pub enum QualityEvaluation {
     StaticCheck,
     License,
     Measure,
+    /// Git churn × complexity hotspots, not scored.
+    Churn,
+    /// Token-based clones of the sources.
+    Duplication,
+    /// Documentation of the public items of the library.
+    Doc,
 }
 
 impl QualityEvaluation {
+    /// The items checked when `check_quality_item` is not set. `Churn`, `Duplication` and
+    /// `Doc` have to be listed.
     pub fn get_all() -> Vec<QualityEvaluation> {
         vec![
             QualityEvaluation::StaticCheck,
@@ -42,6 +55,18 @@ impl QualityEvaluation {
             QualityEvaluation::Measure,
         ]
     }
+
+    /// Name of the check item, as used in the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            QualityEvaluation::StaticCheck => "static_check",
+            QualityEvaluation::License => "license",
+            QualityEvaluation::Measure => "measure",
+            QualityEvaluation::Churn => "churn",
+            QualityEvaluation::Duplication => "duplication",
+            QualityEvaluation::Doc => "doc",
+        }
+    }
 }
 
 impl std::str::FromStr for QualityEvaluation {
@@ -52,34 +77,148 @@ impl std::str::FromStr for QualityEvaluation {
             "static_check" => Self::StaticCheck,
             "license" => Self::License,
             "measure" => Self::Measure,
+            "churn" => Self::Churn,
+            "duplication" => Self::Duplication,
+            "doc" => Self::Doc,
             _ => anyhow::bail!("unkonw QualityEvaluation option"),
         })
     }
 }
 
-#[derive(Debug, Deserialize, Default, Clone)]
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
 pub struct Config {
+    /// Version of the config format, 1 when absent. Older configs are migrated on load.
+    pub version: Option<u32>,
+    /// Base configs, relative to this file. Merged before this file, later ones win.
+    pub extends: Option<Vec<PathBuf>>,
     pub check_quality_item: Option<Vec<QualityEvaluation>>,
+    /// Glob patterns, relative to the project dir, of files excluded from source discovery.
     pub exclude_dir: Option<Vec<String>>,
+    pub source_cfg: Option<SourceConfig>,
     pub check_community_ecology_item: Option<Vec<CommunityEcology>>,
     pub community_ecology_cfg: Option<CommunityEcologyConfig>,
     pub quality_evaluation_cfg: Option<QualityEvaluationConfig>,
     pub project_info_config: Option<ProjectInfoConfig>,
+    pub timeout_cfg: Option<TimeoutConfig>,
+    pub workspace_cfg: Option<WorkspaceConfig>,
+    pub cache_cfg: Option<CacheConfig>,
+}
+
+/// On-disk cache of the measure analysis of each source file, reused while the file and
+/// the rust-code-analysis revision don't change.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct CacheConfig {
+    /// Default true.
+    pub enabled: Option<bool>,
+    /// Cache dir, relative to the workspace root. `<target dir>/cargo-quality/measure-cache`
+    /// by default. Runs may share it.
+    pub dir: Option<PathBuf>,
+}
+
+/// How the workspace score is aggregated from the member scores.
+#[derive(
+    clap::ValueEnum, Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize, Default, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    /// Weighted by the source lines of each member.
+    #[default]
+    SlocWeighted,
+    /// Simple average of the members.
+    Average,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct WorkspaceConfig {
+    pub aggregation: Option<Aggregation>,
+}
+
+/// Which sources are measured. A missing target kind means included.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct SourceConfig {
+    pub lib: Option<bool>,
+    pub bin: Option<bool>,
+    pub example: Option<bool>,
+    pub test: Option<bool>,
+    pub bench: Option<bool>,
+    pub build_script: Option<bool>,
+    /// Skip files matched by `.gitignore` and `.ignore` files, default true.
+    pub respect_ignore: Option<bool>,
+    /// Skip files with an `@generated` marker in their header, default true.
+    pub generated_marker: Option<bool>,
+    /// Glob patterns, relative to the project dir, of generated files.
+    pub generated_files: Option<Vec<String>>,
+}
+
+impl SourceConfig {
+    /// Whether a target with the cargo target `kind` is included.
+    pub fn includes(&self, kind: &str) -> bool {
+        match kind {
+            "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro" => self.lib,
+            "bin" => self.bin,
+            "example" => self.example,
+            "test" => self.test,
+            "bench" => self.bench,
+            "custom-build" => self.build_script,
+            _ => None,
+        }
+        .unwrap_or(true)
+    }
 }
 
-#[derive(Debug, Deserialize, Default, Clone)]
+/// Timeouts in seconds. A missing value means no timeout.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct TimeoutConfig {
+    pub static_check: Option<u64>,
+    pub license: Option<u64>,
+    pub measure: Option<u64>,
+    pub churn: Option<u64>,
+    pub duplication: Option<u64>,
+    pub doc: Option<u64>,
+    /// For each external command, e.g. `cargo clippy`.
+    pub command: Option<u64>,
+}
+
+impl Config {
+    /// Timeout of the check item.
+    pub fn check_item_timeout(&self, q: &QualityEvaluation) -> Option<Duration> {
+        let t = self.timeout_cfg.as_ref()?;
+        match q {
+            QualityEvaluation::StaticCheck => t.static_check,
+            QualityEvaluation::License => t.license,
+            QualityEvaluation::Measure => t.measure,
+            QualityEvaluation::Churn => t.churn,
+            QualityEvaluation::Duplication => t.duplication,
+            QualityEvaluation::Doc => t.doc,
+        }
+        .map(Duration::from_secs)
+    }
+
+    /// Timeout of each external command.
+    pub fn command_timeout(&self) -> Option<Duration> {
+        self.timeout_cfg
+            .as_ref()
+            .and_then(|t| t.command)
+            .map(Duration::from_secs)
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
 pub struct CommunityEcologyConfig {
     pub name: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Default, Clone)]
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
 pub struct QualityEvaluationConfig {
     pub static_check_cfg: Option<StaticCheckEvaluationConfig>,
-    pub measeure_cfg: Option<MeasureEvaluationConfig>,
+    pub measure_cfg: Option<MeasureEvaluationConfig>,
     pub license_cfg: Option<LicenseEvaluationConfig>,
+    pub churn_cfg: Option<ChurnEvaluationConfig>,
+    pub duplication_cfg: Option<DuplicationEvaluationConfig>,
+    pub doc_check_cfg: Option<DocEvaluationConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct StaticCheckEvaluationConfig {
     pub error_score: Option<u64>,
     pub warn_score: Option<u64>,
@@ -87,7 +226,7 @@ pub struct StaticCheckEvaluationConfig {
     pub static_check_weight: Option<u64>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct MeasureEvaluationConfig {
     pub large_cyclomatic_complexity: Option<u64>,
     pub large_cyclomatic_complexity_score: Option<u64>,
@@ -97,11 +236,359 @@ pub struct MeasureEvaluationConfig {
     pub large_num_rows_function_score: Option<u64>,
     pub large_num_rows_file: Option<u64>,
     pub large_num_rows_file_score: Option<u64>,
+    /// Lines counted against `large_num_rows_function` and `large_num_rows_file`.
+    pub size_measure: Option<SizeMeasure>,
+    /// Halstead volume of a function. The metrics below are checked only when set.
+    pub large_halstead_volume: Option<u64>,
+    pub large_halstead_volume_score: Option<u64>,
+    /// Maintainability index of a function, 0 to 100, lower is worse.
+    pub small_maintainability_index: Option<u64>,
+    pub small_maintainability_index_score: Option<u64>,
+    /// ABC magnitude of a function.
+    pub large_abc: Option<u64>,
+    pub large_abc_score: Option<u64>,
+    /// Number of functions and closures of a file.
+    pub large_nom: Option<u64>,
+    pub large_nom_score: Option<u64>,
+    /// Number of arguments of a function.
+    pub large_nargs: Option<u64>,
+    pub large_nargs_score: Option<u64>,
+    /// Number of exit points of a function.
+    pub large_nexits: Option<u64>,
+    pub large_nexits_score: Option<u64>,
     pub measure_score: Option<u64>,
     pub measure_weight: Option<u64>,
+    /// Thresholds for the files matching a glob. The most specific matching block applies.
+    pub overrides: Option<Vec<MeasureOverride>>,
+    /// How the penalty of each metric grows past its threshold, `step` by default.
+    pub penalty: Option<MeasurePenaltyConfig>,
+    /// Thresholds and weight of each class of code, e.g. tests.
+    pub classes: Option<MeasureClassesConfig>,
+    /// Number of functions and files in the hotspot list of the result, 10 by default.
+    pub top_hotspots: Option<usize>,
+}
+
+impl MeasureEvaluationConfig {
+    /// Threshold of the metric, e.g. `large_nargs` for `nargs`.
+    pub fn threshold(&self, metric: &str) -> Option<u64> {
+        match metric {
+            "cyclomatic_complexity" => self.large_cyclomatic_complexity,
+            "cognitive_complexity" => self.large_cognitive_complexity,
+            "num_rows_function" => self.large_num_rows_function,
+            "num_rows_file" => self.large_num_rows_file,
+            "halstead_volume" => self.large_halstead_volume,
+            "maintainability_index" => self.small_maintainability_index,
+            "abc" => self.large_abc,
+            "nom" => self.large_nom,
+            "nargs" => self.large_nargs,
+            "nexits" => self.large_nexits,
+            _ => None,
+        }
+    }
+
+    /// Penalty of a finding of the metric at its threshold.
+    pub fn score(&self, metric: &str) -> Option<u64> {
+        match metric {
+            "cyclomatic_complexity" => self.large_cyclomatic_complexity_score,
+            "cognitive_complexity" => self.large_cognitive_complexity_score,
+            "num_rows_function" => self.large_num_rows_function_score,
+            "num_rows_file" => self.large_num_rows_file_score,
+            "halstead_volume" => self.large_halstead_volume_score,
+            "maintainability_index" => self.small_maintainability_index_score,
+            "abc" => self.large_abc_score,
+            "nom" => self.large_nom_score,
+            "nargs" => self.large_nargs_score,
+            "nexits" => self.large_nexits_score,
+            _ => None,
+        }
+    }
+}
+
+/// Which lines make up the size of a function or file.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeMeasure {
+    /// All the lines of the span, with comments and blank lines.
+    Lines,
+    /// Source lines as rust-code-analysis counts them: every line of the span, comments and
+    /// blank lines included.
+    Sloc,
+    /// Physical lines of code, without comments and blank lines.
+    #[default]
+    Ploc,
+    /// Logical lines, i.e. statements.
+    Lloc,
+}
+
+/// Penalty of each metric of the measure check, named like the suppression rules.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct MeasurePenaltyConfig {
+    pub cyclomatic_complexity: Option<Penalty>,
+    pub cognitive_complexity: Option<Penalty>,
+    pub num_rows_function: Option<Penalty>,
+    pub num_rows_file: Option<Penalty>,
+    pub halstead_volume: Option<Penalty>,
+    pub maintainability_index: Option<Penalty>,
+    pub abc: Option<Penalty>,
+    pub nom: Option<Penalty>,
+    pub nargs: Option<Penalty>,
+    pub nexits: Option<Penalty>,
+}
+
+impl MeasurePenaltyConfig {
+    /// Penalty of the metric, the default `step` if not configured.
+    pub fn get(&self, metric: &str) -> Penalty {
+        let p = match metric {
+            "cyclomatic_complexity" => &self.cyclomatic_complexity,
+            "cognitive_complexity" => &self.cognitive_complexity,
+            "num_rows_function" => &self.num_rows_function,
+            "num_rows_file" => &self.num_rows_file,
+            "halstead_volume" => &self.halstead_volume,
+            "maintainability_index" => &self.maintainability_index,
+            "abc" => &self.abc,
+            "nom" => &self.nom,
+            "nargs" => &self.nargs,
+            "nexits" => &self.nexits,
+            _ => &None,
+        };
+        p.clone().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct Penalty {
+    pub curve: Option<PenaltyCurve>,
+    /// Largest penalty of a single finding.
+    pub cap: Option<u64>,
+}
+
+/// How the penalty of a finding grows with `value / threshold`, or `threshold / value` for
+/// the `small_*` thresholds. All curves cost the `*_score` of the metric at the threshold.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PenaltyCurve {
+    /// The `*_score` for every finding, however far past the threshold.
+    #[default]
+    Step,
+    /// `score * value / threshold`
+    Linear,
+    /// `score * (value / threshold)^2`
+    Quadratic,
+    /// `score * (1 + ln(value / threshold))`
+    Logarithmic,
+}
+
+impl PenaltyCurve {
+    /// Penalty of a finding `ratio` times past the threshold.
+    pub fn penalty(&self, score: u64, ratio: f64) -> u64 {
+        let factor = match self {
+            PenaltyCurve::Step => 1.0,
+            PenaltyCurve::Linear => ratio,
+            PenaltyCurve::Quadratic => ratio * ratio,
+            PenaltyCurve::Logarithmic => 1.0 + ratio.ln().max(0.0),
+        };
+        (score as f64 * factor).round() as u64
+    }
+}
+
+/// Class of a measured function or file, from its cargo target and its attributes.
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Copy,
+    Clone,
+    Deserialize,
+    Serialize,
+    Default,
+    JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeClass {
+    #[default]
+    Production,
+    /// Integration tests, `#[test]` functions and `#[cfg(test)]` modules.
+    Test,
+    /// Benchmarks and `#[bench]` functions.
+    Bench,
+    Example,
+    /// The `build.rs` of a package.
+    BuildScript,
+}
+
+impl CodeClass {
+    pub const ALL: &'static [CodeClass] = &[
+        CodeClass::Production,
+        CodeClass::Test,
+        CodeClass::Bench,
+        CodeClass::Example,
+        CodeClass::BuildScript,
+    ];
+}
+
+/// Measure config of each class of code. A class without one is measured like production code.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct MeasureClassesConfig {
+    pub production: Option<MeasureClassConfig>,
+    pub test: Option<MeasureClassConfig>,
+    pub bench: Option<MeasureClassConfig>,
+    pub example: Option<MeasureClassConfig>,
+    pub build_script: Option<MeasureClassConfig>,
+}
+
+impl MeasureClassesConfig {
+    pub fn get(&self, class: CodeClass) -> Option<&MeasureClassConfig> {
+        match class {
+            CodeClass::Production => self.production.as_ref(),
+            CodeClass::Test => self.test.as_ref(),
+            CodeClass::Bench => self.bench.as_ref(),
+            CodeClass::Example => self.example.as_ref(),
+            CodeClass::BuildScript => self.build_script.as_ref(),
+        }
+    }
+}
+
+/// Thresholds and weight of a class of code.
+/// Thresholds left out fall back to the ones of `measure_cfg`, path overrides take precedence.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct MeasureClassConfig {
+    /// Leave the class out of the measure score, its functions are still listed.
+    pub exclude: Option<bool>,
+    /// Percent of the penalty of its findings charged to the score, 100 by default.
+    pub weight: Option<u64>,
+    pub large_cyclomatic_complexity: Option<u64>,
+    pub large_cognitive_complexity: Option<u64>,
+    pub large_num_rows_function: Option<u64>,
+    pub large_num_rows_file: Option<u64>,
+    pub large_halstead_volume: Option<u64>,
+    pub small_maintainability_index: Option<u64>,
+    pub large_abc: Option<u64>,
+    pub large_nom: Option<u64>,
+    pub large_nargs: Option<u64>,
+    pub large_nexits: Option<u64>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl MeasureClassConfig {
+    /// Threshold of the metric, like `MeasureEvaluationConfig::threshold`.
+    pub fn threshold(&self, metric: &str) -> Option<u64> {
+        match metric {
+            "cyclomatic_complexity" => self.large_cyclomatic_complexity,
+            "cognitive_complexity" => self.large_cognitive_complexity,
+            "num_rows_function" => self.large_num_rows_function,
+            "num_rows_file" => self.large_num_rows_file,
+            "halstead_volume" => self.large_halstead_volume,
+            "maintainability_index" => self.small_maintainability_index,
+            "abc" => self.large_abc,
+            "nom" => self.large_nom,
+            "nargs" => self.large_nargs,
+            "nexits" => self.large_nexits,
+            _ => None,
+        }
+    }
+}
+
+/// Thresholds of the measure check for the files matching `path`.
+/// Thresholds left out fall back to the ones of `measure_cfg`.
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+pub struct MeasureOverride {
+    /// Glob pattern, relative to the package dir, e.g. `tests/**`.
+    pub path: String,
+    pub large_cyclomatic_complexity: Option<u64>,
+    pub large_cognitive_complexity: Option<u64>,
+    pub large_num_rows_function: Option<u64>,
+    pub large_num_rows_file: Option<u64>,
+    pub large_halstead_volume: Option<u64>,
+    pub small_maintainability_index: Option<u64>,
+    pub large_abc: Option<u64>,
+    pub large_nom: Option<u64>,
+    pub large_nargs: Option<u64>,
+    pub large_nexits: Option<u64>,
+}
+
+impl MeasureOverride {
+    /// Threshold of the metric, like `MeasureEvaluationConfig::threshold`.
+    pub fn threshold(&self, metric: &str) -> Option<u64> {
+        match metric {
+            "cyclomatic_complexity" => self.large_cyclomatic_complexity,
+            "cognitive_complexity" => self.large_cognitive_complexity,
+            "num_rows_function" => self.large_num_rows_function,
+            "num_rows_file" => self.large_num_rows_file,
+            "halstead_volume" => self.large_halstead_volume,
+            "maintainability_index" => self.small_maintainability_index,
+            "abc" => self.large_abc,
+            "nom" => self.large_nom,
+            "nargs" => self.large_nargs,
+            "nexits" => self.large_nexits,
+            _ => None,
+        }
+    }
+
+    /// How specific the pattern is: the number of its literal characters.
+    /// A block with the same specificity as an earlier one wins over it.
+    pub fn specificity(&self) -> usize {
+        self.path
+            .chars()
+            .filter(|c| !matches!(c, '*' | '?' | '[' | ']' | '{' | '}' | '!'))
+            .count()
+    }
+}
+
+/// Git history window and hotspot thresholds of the churn check.
+/// The complexity of the files comes from the functions measured with `measure_cfg`.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct ChurnEvaluationConfig {
+    /// Days of history before `HEAD` to read, 180 by default.
+    pub since_days: Option<u64>,
+    /// Most commits to read, all the commits of the window by default.
+    pub max_commits: Option<usize>,
+    /// Commits in the window from which a file is high-churn, 10 by default.
+    pub high_churn: Option<u64>,
+    /// Cognitive complexity of the functions of a file, summed, from which the file is
+    /// high-complexity, 50 by default.
+    pub high_complexity: Option<u64>,
+    /// Number of files in the hotspot list, 10 by default.
+    pub top_hotspots: Option<usize>,
+}
+
+/// Clone detection over the token streams of the sources, identifiers and literals normalized.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct DuplicationEvaluationConfig {
+    /// Fewest tokens of a duplicated block, 50 by default.
+    pub min_tokens: Option<usize>,
+    /// Number of blocks in `code_duplication.blocks` of the result, the largest first,
+    /// 10 by default.
+    pub top_blocks: Option<usize>,
+    /// Cost of each percent of duplicated lines in the member.
+    pub duplicated_percent_score: Option<u64>,
+    pub duplication_score: Option<u64>,
+    pub duplication_weight: Option<u64>,
+}
+
+/// Documentation of the public items of the library target: functions, structs, enums,
+/// traits, modules, consts and exported macros.
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
+pub struct DocEvaluationConfig {
+    /// Cost of each public item without a doc comment.
+    pub undocumented_item_score: Option<u64>,
+    /// Cost of a library without crate-level docs, 0 by default.
+    pub missing_crate_doc_score: Option<u64>,
+    /// Cost of each public function without an `# Examples` section, 0 by default.
+    pub missing_example_score: Option<u64>,
+    /// Cost of a library which doesn't deny or forbid `missing_docs`, 0 by default.
+    pub missing_docs_lint_score: Option<u64>,
+    /// Cost of each rustdoc diagnostic of `cargo doc`, e.g. a broken intra-doc link,
+    /// 0 by default.
+    pub doc_warning_score: Option<u64>,
+    /// Cost of docs which fail to build, `doc_score` by default.
+    pub doc_build_failure_score: Option<u64>,
+    pub doc_score: Option<u64>,
+    pub doc_weight: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 pub struct LicenseEvaluationConfig {
     pub allow_licenses: Option<Vec<String>>,
     pub deny_licenses: Option<Vec<String>>,
@@ -113,14 +600,224 @@ pub struct LicenseEvaluationConfig {
 }
 
 pub fn parse(path: PathBuf) -> Result<Config> {
-    let content = std::fs::read(&path)
+    let value = load(&path, &mut vec![])?;
+    value.try_into().map_err(anyhow::Error::from)
+}
+
+pub const CONFIG_FILE: &str = "quality-evaluation.toml";
+
+/// Key of the base configs, resolved relative to the file which declares them.
+pub const EXTENDS_KEY: &str = "extends";
+
+/// Prefix of the environment variables overriding config values.
+/// `__` separates the keys, e.g. `CARGO_QUALITY_QUALITY_EVALUATION_CFG__STATIC_CHECK_CFG__WARN_SCORE=5`.
+pub const ENV_PREFIX: &str = "CARGO_QUALITY_";
+
+/// Read a config file and merge it on top of the configs it `extends`.
+fn load(path: &Path, visited: &mut Vec<PathBuf>) -> Result<toml::Value> {
+    let content = std::fs::read(path)
         .with_context(|| format!("Failed to read config file from {:?}", path))?;
-    toml::from_str(std::str::from_utf8(&content).unwrap()).map_err(anyhow::Error::from)
+    let value: toml::Value = toml::from_str(std::str::from_utf8(&content)?)
+        .with_context(|| format!("Failed to parse config file {:?}", path))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    resolve_extends(value, dir, path, visited)
+}
+
+/// Merge `value` on top of the configs listed in its `extends`, in order.
+fn resolve_extends(
+    mut value: toml::Value,
+    dir: &Path,
+    path: &Path,
+    visited: &mut Vec<PathBuf>,
+) -> Result<toml::Value> {
+    migrate::migrate_value(&mut value, path)?;
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    anyhow::ensure!(
+        !visited.contains(&canonical),
+        "Config {:?} extends itself: {:?}",
+        path,
+        visited
+    );
+    visited.push(canonical);
+
+    let extends = match value.as_table_mut().and_then(|t| t.remove(EXTENDS_KEY)) {
+        Some(toml::Value::Array(a)) => a,
+        Some(toml::Value::String(s)) => vec![toml::Value::String(s)],
+        Some(v) => anyhow::bail!("`extends` in {:?} must be a list of paths, got {}", path, v),
+        None => vec![],
+    };
+    let mut merged = toml::Value::Table(Default::default());
+    for e in extends {
+        let base = e
+            .as_str()
+            .with_context(|| format!("`extends` in {:?} must be a list of paths", path))?;
+        merge(&mut merged, load(&dir.join(base), visited)?);
+    }
+    merge(&mut merged, value);
+
+    visited.pop();
+    Ok(merged)
+}
+
+/// Deep merge `top` into `base`. Tables are merged key by key, other values are replaced.
+pub fn merge(base: &mut toml::Value, top: toml::Value) {
+    match (base, top) {
+        (toml::Value::Table(b), toml::Value::Table(t)) => {
+            for (k, v) in t {
+                match b.get_mut(&k) {
+                    Some(bv) => merge(bv, v),
+                    None => {
+                        b.insert(k, v);
+                    }
+                }
+            }
+        }
+        (b, t) => *b = t,
+    }
+}
+
+/// Set the value at the dotted key path, creating the missing tables.
+fn set_path(root: &mut toml::Value, path: &str, value: toml::Value) -> Result<()> {
+    let keys: Vec<&str> = path.split('.').collect();
+    anyhow::ensure!(
+        keys.iter().all(|k| !k.is_empty()),
+        "Invalid key path: {:?}",
+        path
+    );
+    let mut cur = root;
+    for k in &keys[..keys.len() - 1] {
+        let t = cur
+            .as_table_mut()
+            .with_context(|| format!("Key path {:?} goes through a non-table value", path))?;
+        cur = t
+            .entry(k.to_string())
+            .or_insert_with(|| toml::Value::Table(Default::default()));
+    }
+    let t = cur
+        .as_table_mut()
+        .with_context(|| format!("Key path {:?} goes through a non-table value", path))?;
+    t.insert(keys[keys.len() - 1].to_string(), value);
+    Ok(())
+}
+
+/// Parse an override value as TOML, e.g. `5`, `true` or `["MIT"]`, else take it as a string.
+fn parse_override_value(s: &str) -> toml::Value {
+    toml::from_str::<toml::value::Table>(&format!("v = {}", s))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(s.to_string()))
+}
+
+/// Apply the `CARGO_QUALITY_*` environment variables, then the `--set key=value` overrides.
+pub fn apply_overrides(value: &mut toml::Value, set: &[String]) -> Result<()> {
+    let mut env: Vec<(String, String)> = std::env::vars()
+        .filter_map(|(k, v)| k.strip_prefix(ENV_PREFIX).map(|p| (p.to_string(), v)))
+        .collect();
+    env.sort();
+    for (k, v) in env {
+        let path = k.to_ascii_lowercase().replace("__", ".");
+        set_path(value, &path, parse_override_value(&v))
+            .with_context(|| format!("Invalid environment override {}{}", ENV_PREFIX, k))?;
+    }
+    for s in set {
+        let (k, v) = s
+            .split_once('=')
+            .with_context(|| format!("Invalid override {:?}, expect `key.path=value`", s))?;
+        set_path(value, k.trim(), parse_override_value(v.trim()))
+            .with_context(|| format!("Invalid override {:?}", s))?;
+    }
+    Ok(())
+}
+
+/// Where the config was loaded from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "path", rename_all = "snake_case")]
+pub enum ConfigSource {
+    /// `--config`
+    Arg(PathBuf),
+    /// `[package.metadata.quality]` of the manifest.
+    PackageMetadata(PathBuf),
+    /// `[workspace.metadata.quality]` of the manifest.
+    WorkspaceMetadata(PathBuf),
+    /// `quality-evaluation.toml` in the project dir or one of its parents.
+    File(PathBuf),
+}
+
+/// Find and parse the config of the project, with the overrides applied.
+pub fn discover(
+    arg: Option<PathBuf>,
+    project_dir: &Path,
+    set: &[String],
+) -> Result<(Config, ConfigSource)> {
+    let (value, source) = discover_value(arg, project_dir, set)?;
+    let cfg = value
+        .try_into()
+        .with_context(|| format!("Failed to parse the config from {:?}", source))?;
+    Ok((cfg, source))
+}
+
+/// Find the effective config of the project, in order of precedence:
+/// `--config`, `[package.metadata.quality]` or `[workspace.metadata.quality]` of the
+/// project manifest, then `quality-evaluation.toml` in the project dir or its nearest parent.
+/// `extends` is resolved, then the overrides from `apply_overrides` are applied.
+pub fn discover_value(
+    arg: Option<PathBuf>,
+    project_dir: &Path,
+    set: &[String],
+) -> Result<(toml::Value, ConfigSource)> {
+    let (mut value, source) = find_value(arg, project_dir)?;
+    apply_overrides(&mut value, set)?;
+    Ok((value, source))
+}
+
+fn find_value(arg: Option<PathBuf>, project_dir: &Path) -> Result<(toml::Value, ConfigSource)> {
+    if let Some(p) = arg {
+        return Ok((load(&p, &mut vec![])?, ConfigSource::Arg(p)));
+    }
+
+    let manifest = project_dir.join("Cargo.toml");
+    if let Ok(content) = std::fs::read_to_string(&manifest) {
+        let value: toml::Value = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse manifest {:?}", manifest))?;
+        for (table, source) in [
+            ("package", ConfigSource::PackageMetadata(manifest.clone())),
+            (
+                "workspace",
+                ConfigSource::WorkspaceMetadata(manifest.clone()),
+            ),
+        ] {
+            if let Some(q) = value
+                .get(table)
+                .and_then(|t| t.get("metadata"))
+                .and_then(|m| m.get("quality"))
+            {
+                let q = resolve_extends(q.clone(), project_dir, &manifest, &mut vec![])?;
+                return Ok((q, source));
+            }
+        }
+    }
+
+    // A relative dir, e.g. `.`, has no ancestors above the current dir.
+    let project_dir = project_dir
+        .canonicalize()
+        .unwrap_or_else(|_| project_dir.to_path_buf());
+    for dir in project_dir.ancestors() {
+        let p = dir.join(CONFIG_FILE);
+        if p.is_file() {
+            return Ok((load(&p, &mut vec![])?, ConfigSource::File(p)));
+        }
+    }
+    anyhow::bail!(
+        "No config found for {:?}, run `cargo quality init` or pass --config",
+        project_dir
+    )
 }
 
-#[derive(Debug, Deserialize, Default, Clone)]
+#[derive(Debug, Deserialize, Default, Clone, JsonSchema)]
 pub struct ProjectInfoConfig {
     pub manifest: PathBuf,
     pub dir: PathBuf,
     pub result: PathBuf,
+    /// The workspace member under evaluation.
+    pub package: Option<String>,
 }
@@ -0,0 +1,710 @@
+use super::{migrate, Config, ConfigSource, QualityEvaluation, EXTENDS_KEY};
+use anyhow::{Context, Result};
+use schemars::schema::{RootSchema, Schema, SchemaObject, SingleOrVec};
+use schemars::Map;
+use std::path::{Path, PathBuf};
+
+/// Keys of each check item which its score can't be computed without.
+const REQUIRED_KEYS: &[(QualityEvaluation, &str, &[&str])] = &[
+    (
+        QualityEvaluation::StaticCheck,
+        "static_check_cfg",
+        &[
+            "error_score",
+            "warn_score",
+            "static_check_score",
+            "static_check_weight",
+        ],
+    ),
+    (
+        QualityEvaluation::Measure,
+        "measure_cfg",
+        &[
+            "large_cyclomatic_complexity",
+            "large_cyclomatic_complexity_score",
+            "large_cognitive_complexity",
+            "large_cognitive_complexity_score",
+            "large_num_rows_function",
+            "large_num_rows_function_score",
+            "large_num_rows_file",
+            "large_num_rows_file_score",
+            "measure_score",
+            "measure_weight",
+        ],
+    ),
+    (
+        QualityEvaluation::License,
+        "license_cfg",
+        &[
+            "allow_licenses",
+            "deny_licenses",
+            "deny_license_score",
+            "default_license_score",
+            "license_eval_score",
+            "license_eval_weight",
+        ],
+    ),
+    (
+        QualityEvaluation::Duplication,
+        "duplication_cfg",
+        &[
+            "duplicated_percent_score",
+            "duplication_score",
+            "duplication_weight",
+        ],
+    ),
+    (
+        QualityEvaluation::Doc,
+        "doc_check_cfg",
+        &["undocumented_item_score", "doc_score", "doc_weight"],
+    ),
+];
+
+/// Total score and weight keys of each check item.
+const SCORE_KEYS: &[(QualityEvaluation, &str, &str, &str)] = &[
+    (
+        QualityEvaluation::StaticCheck,
+        "static_check_cfg",
+        "static_check_score",
+        "static_check_weight",
+    ),
+    (
+        QualityEvaluation::Measure,
+        "measure_cfg",
+        "measure_score",
+        "measure_weight",
+    ),
+    (
+        QualityEvaluation::License,
+        "license_cfg",
+        "license_eval_score",
+        "license_eval_weight",
+    ),
+    (
+        QualityEvaluation::Duplication,
+        "duplication_cfg",
+        "duplication_score",
+        "duplication_weight",
+    ),
+    (
+        QualityEvaluation::Doc,
+        "doc_check_cfg",
+        "doc_score",
+        "doc_weight",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the config, located by file and line when possible.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+            }
+            write!(f, ": ")?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// JSON Schema of the config file.
+pub fn schema() -> RootSchema {
+    schemars::schema_for!(Config)
+}
+
+/// A config file and the key prefix of the config inside it.
+struct SourceFile {
+    path: PathBuf,
+    text: String,
+    prefix: Vec<String>,
+    value: toml::Value,
+}
+
+impl SourceFile {
+    fn line_of(&self, path: &[String]) -> Option<usize> {
+        let mut full = self.prefix.clone();
+        full.extend(path.iter().cloned());
+        find_line(&self.text, &full)
+    }
+}
+
+/// Validate the effective config of the project.
+pub fn validate(
+    arg: Option<PathBuf>,
+    project_dir: &Path,
+    set: &[String],
+) -> Result<Vec<Diagnostic>> {
+    let (value, source) = super::discover_value(arg, project_dir, set)?;
+    let files = source_files(&source)?;
+    let root = schema();
+    let mut out = vec![];
+
+    // Unknown keys are reported in the file declaring them.
+    for f in files.iter() {
+        let version = migrate::version_of(&f.value)?;
+        if version < migrate::CURRENT_VERSION {
+            out.push(Diagnostic {
+                severity: Severity::Warning,
+                file: Some(f.path.clone()),
+                line: None,
+                message: format!(
+                    "config version {} is outdated, run `cargo quality config migrate` to upgrade it to {}",
+                    version,
+                    migrate::CURRENT_VERSION
+                ),
+            });
+        }
+        let mut value = f.value.clone();
+        migrate::migrate_value(&mut value, &f.path)?;
+        let mut unknown = vec![];
+        check_keys(
+            &value,
+            &root.schema,
+            &root.definitions,
+            &mut vec![],
+            &mut unknown,
+        );
+        for (path, suggestion) in unknown {
+            let mut message = format!("unknown key `{}`", key_name(&path));
+            if let Some(s) = suggestion {
+                message += &format!(", did you mean `{}`?", s);
+            }
+            out.push(Diagnostic {
+                severity: Severity::Error,
+                file: Some(f.path.clone()),
+                line: f.line_of(&path),
+                message,
+            });
+        }
+    }
+
+    // The other checks are about the merged config, reported where the key is set last.
+    let locate = |path: &[&str]| -> (Option<PathBuf>, Option<usize>) {
+        let path: Vec<String> = path.iter().map(|s| s.to_string()).collect();
+        for p in (1..=path.len()).rev() {
+            if let Some((f, l)) = files
+                .iter()
+                .find_map(|f| f.line_of(&path[..p]).map(|l| (f, l)))
+            {
+                return (Some(f.path.clone()), Some(l));
+            }
+        }
+        (files.first().map(|f| f.path.clone()), None)
+    };
+    let mut report = |severity, path: &[&str], message: String| {
+        let (file, line) = locate(path);
+        out.push(Diagnostic {
+            severity,
+            file,
+            line,
+            message,
+        });
+    };
+
+    let cfg: Config = match value.clone().try_into() {
+        Ok(c) => c,
+        Err(e) => {
+            report(Severity::Error, &[], format!("invalid config: {}", e));
+            return Ok(out);
+        }
+    };
+    let items = cfg
+        .check_quality_item
+        .clone()
+        .unwrap_or_else(QualityEvaluation::get_all);
+
+    for (item, table, keys) in REQUIRED_KEYS {
+        if !items.contains(item) {
+            continue;
+        }
+        for key in keys.iter() {
+            if get(&value, table, key).is_none() {
+                report(
+                    Severity::Error,
+                    &["quality_evaluation_cfg", *table],
+                    format!(
+                        "missing `quality_evaluation_cfg.{}.{}`, the {} check is not scored without it",
+                        table,
+                        key,
+                        item.name()
+                    ),
+                );
+            }
+        }
+    }
+
+    let mut weight_sum = 0;
+    for (item, table, score_key, weight_key) in SCORE_KEYS {
+        if !items.contains(item) {
+            continue;
+        }
+        if let Some(score) = get(&value, table, score_key).and_then(|v| v.as_integer()) {
+            if score <= 0 {
+                report(
+                    Severity::Error,
+                    &["quality_evaluation_cfg", *table, *score_key],
+                    format!("`{}` must be greater than 0, got {}", score_key, score),
+                );
+            }
+            // A single finding should not cost more than the whole score.
+            if let Some(toml::Value::Table(t)) = value
+                .get("quality_evaluation_cfg")
+                .and_then(|q| q.get(table))
+            {
+                for (k, v) in t.iter() {
+                    match v.as_integer() {
+                        Some(p)
+                            if k.ends_with("_score") && k.as_str() != *score_key && p > score =>
+                        {
+                            report(
+                                Severity::Warning,
+                                &["quality_evaluation_cfg", *table, k.as_str()],
+                                format!(
+                                    "`{}` = {} is greater than `{}` = {}",
+                                    k, p, score_key, score
+                                ),
+                            )
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        if let Some(weight) = get(&value, table, weight_key).and_then(|v| v.as_integer()) {
+            if !(0..=100).contains(&weight) {
+                report(
+                    Severity::Error,
+                    &["quality_evaluation_cfg", *table, *weight_key],
+                    format!("`{}` must be in 0..=100, got {}", weight_key, weight),
+                );
+            }
+            weight_sum += weight;
+        }
+    }
+    if weight_sum != 100 {
+        report(
+            Severity::Error,
+            &["quality_evaluation_cfg"],
+            format!(
+                "the weights of the enabled check items sum to {}, expect 100",
+                weight_sum
+            ),
+        );
+    }
+
+    if let Some(m) = value
+        .get("quality_evaluation_cfg")
+        .and_then(|q| q.get("measure_cfg"))
+        .and_then(|m| m.as_table())
+    {
+        for (k, v) in m.iter() {
+            let threshold =
+                (k.starts_with("large_") || k.starts_with("small_")) && !k.ends_with("_score");
+            if !threshold {
+                continue;
+            }
+            if let Some(t) = v.as_integer() {
+                if t <= 0 {
+                    report(
+                        Severity::Error,
+                        &["quality_evaluation_cfg", "measure_cfg", k.as_str()],
+                        format!("threshold `{}` must be greater than 0, got {}", k, t),
+                    );
+                }
+            }
+            let score_key = format!("{}_score", k);
+            if !m.contains_key(&score_key) {
+                report(
+                    Severity::Warning,
+                    &["quality_evaluation_cfg", "measure_cfg", k.as_str()],
+                    format!(
+                        "threshold `{}` has no `{}`, its findings cost nothing",
+                        k, score_key
+                    ),
+                );
+            }
+        }
+        for o in m
+            .get("overrides")
+            .and_then(|o| o.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let pattern = o.get("path").and_then(|p| p.as_str()).unwrap_or_default();
+            if let Err(e) = globset::Glob::new(pattern) {
+                report(
+                    Severity::Error,
+                    &["quality_evaluation_cfg", "measure_cfg", "overrides"],
+                    format!("invalid override path {:?}: {}", pattern, e),
+                );
+            }
+            for (k, v) in o.as_table().into_iter().flatten() {
+                if let Some(t) = v.as_integer().filter(|t| *t <= 0) {
+                    report(
+                        Severity::Error,
+                        &["quality_evaluation_cfg", "measure_cfg", "overrides"],
+                        format!(
+                            "threshold `{}` of the override {:?} must be greater than 0, got {}",
+                            k, pattern, t
+                        ),
+                    );
+                }
+            }
+        }
+        for (class, c) in m
+            .get("classes")
+            .and_then(|c| c.as_table())
+            .into_iter()
+            .flatten()
+        {
+            for (k, v) in c.as_table().into_iter().flatten() {
+                let t = match v.as_integer() {
+                    Some(t) => t,
+                    None => continue,
+                };
+                if k == "weight" && !(0..=100).contains(&t) {
+                    report(
+                        Severity::Error,
+                        &[
+                            "quality_evaluation_cfg",
+                            "measure_cfg",
+                            "classes",
+                            class.as_str(),
+                        ],
+                        format!(
+                            "`weight` of the class `{}` must be in 0..=100, got {}",
+                            class, t
+                        ),
+                    );
+                } else if k != "weight" && t <= 0 {
+                    report(
+                        Severity::Error,
+                        &[
+                            "quality_evaluation_cfg",
+                            "measure_cfg",
+                            "classes",
+                            class.as_str(),
+                        ],
+                        format!(
+                            "threshold `{}` of the class `{}` must be greater than 0, got {}",
+                            k, class, t
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(t) = get(&value, "duplication_cfg", "min_tokens").and_then(|v| v.as_integer()) {
+        if t <= 0 {
+            report(
+                Severity::Error,
+                &["quality_evaluation_cfg", "duplication_cfg", "min_tokens"],
+                format!("`min_tokens` must be greater than 0, got {}", t),
+            );
+        }
+    }
+
+    Ok(out)
+}
+
+fn get<'a>(value: &'a toml::Value, table: &str, key: &str) -> Option<&'a toml::Value> {
+    value
+        .get("quality_evaluation_cfg")
+        .and_then(|q| q.get(table))
+        .and_then(|t| t.get(key))
+}
+
+/// Read the config files which make up the config, the top one first.
+fn source_files(source: &ConfigSource) -> Result<Vec<SourceFile>> {
+    let mut files = vec![];
+    match source {
+        ConfigSource::Arg(p) | ConfigSource::File(p) => read_source(p, vec![], &mut files)?,
+        ConfigSource::PackageMetadata(p) => read_source(
+            p,
+            vec!["package".into(), "metadata".into(), "quality".into()],
+            &mut files,
+        )?,
+        ConfigSource::WorkspaceMetadata(p) => read_source(
+            p,
+            vec!["workspace".into(), "metadata".into(), "quality".into()],
+            &mut files,
+        )?,
+    }
+    Ok(files)
+}
+
+fn read_source(path: &Path, prefix: Vec<String>, files: &mut Vec<SourceFile>) -> Result<()> {
+    if files.iter().any(|f| f.path == path) {
+        return Ok(());
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file from {:?}", path))?;
+    let mut value: toml::Value =
+        toml::from_str(&text).with_context(|| format!("Failed to parse {:?}", path))?;
+    for k in prefix.iter() {
+        value = value
+            .get(k)
+            .cloned()
+            .unwrap_or(toml::Value::Table(Default::default()));
+    }
+    let extends: Vec<String> = value
+        .get(EXTENDS_KEY)
+        .and_then(|e| e.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.push(SourceFile {
+        path: path.to_path_buf(),
+        text,
+        prefix,
+        value,
+    });
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    for e in extends {
+        read_source(&dir.join(e), vec![], files)?;
+    }
+    Ok(())
+}
+
+/// Follow references and `allOf`/`anyOf` wrappers to the schema describing an object, or an
+/// array with `array`.
+fn resolve<'a>(
+    s: &'a SchemaObject,
+    defs: &'a Map<String, Schema>,
+    array: bool,
+) -> Option<&'a SchemaObject> {
+    if let Some(r) = &s.reference {
+        let name = r.trim_start_matches("#/definitions/");
+        return match defs.get(name) {
+            Some(Schema::Object(o)) => resolve(o, defs, array),
+            _ => None,
+        };
+    }
+    if (array && s.array.is_some()) || (!array && s.object.is_some()) {
+        return Some(s);
+    }
+    let sub = s.subschemas.as_ref()?;
+    sub.all_of
+        .iter()
+        .chain(sub.any_of.iter())
+        .flatten()
+        .find_map(|x| match x {
+            Schema::Object(o) => resolve(o, defs, array),
+            _ => None,
+        })
+}
+
+/// Collect the keys of `value` which are not properties in the schema. The elements of an
+/// array of tables are checked against the schema of its items, their index in the path.
+fn check_keys(
+    value: &toml::Value,
+    schema: &SchemaObject,
+    defs: &Map<String, Schema>,
+    path: &mut Vec<String>,
+    unknown: &mut Vec<(Vec<String>, Option<String>)>,
+) {
+    if let Some(elements) = value.as_array() {
+        let items = resolve(schema, defs, true)
+            .and_then(|s| s.array.as_ref())
+            .and_then(|a| a.items.as_ref());
+        if let Some(SingleOrVec::Single(item)) = items {
+            if let Schema::Object(item) = item.as_ref() {
+                for (i, e) in elements.iter().enumerate() {
+                    path.push(i.to_string());
+                    check_keys(e, item, defs, path, unknown);
+                    path.pop();
+                }
+            }
+        }
+        return;
+    }
+    let (table, object) = match (value.as_table(), resolve(schema, defs, false)) {
+        (Some(t), Some(s)) => match &s.object {
+            Some(o) => (t, o),
+            None => return,
+        },
+        _ => return,
+    };
+    for (k, v) in table.iter() {
+        path.push(k.clone());
+        match object.properties.get(k) {
+            Some(Schema::Object(sub)) => check_keys(v, sub, defs, path, unknown),
+            Some(Schema::Bool(_)) => {}
+            None => {
+                let suggestion = object
+                    .properties
+                    .keys()
+                    .map(|p| (edit_distance(k, p), p))
+                    .filter(|(d, _)| *d <= 3)
+                    .min()
+                    .map(|(_, p)| p.clone());
+                unknown.push((path.clone(), suggestion));
+            }
+        }
+        path.pop();
+    }
+}
+
+/// The key path as written in the config, e.g. `measure_cfg.overrides[0].path`.
+fn key_name(path: &[String]) -> String {
+    let mut name = String::new();
+    for k in path {
+        if k.parse::<usize>().is_ok() {
+            name += &format!("[{}]", k);
+        } else {
+            if !name.is_empty() {
+                name.push('.');
+            }
+            name += k;
+        }
+    }
+    name
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Find the 1-based line which declares the key path, either as a table header or a key.
+/// The elements of an array of tables are numbered in the path, in the order of their
+/// `[[..]]` headers; an element of an inline array is located at the key of the array.
+fn find_line(text: &str, path: &[String]) -> Option<usize> {
+    let split = |s: &str| -> Vec<String> {
+        s.split('.')
+            .map(|k| k.trim().trim_matches('"').to_string())
+            .collect()
+    };
+    let mut table: Vec<String> = vec![];
+    let mut arrays: Vec<Vec<String>> = vec![];
+    for (n, l) in text.lines().enumerate() {
+        let l = l.trim();
+        if l.starts_with('#') || l.is_empty() {
+            continue;
+        }
+        if l.starts_with('[') {
+            table = split(l.trim_start_matches('[').split(']').next().unwrap_or(""));
+            if l.starts_with("[[") {
+                let index = arrays.iter().filter(|a| **a == table).count();
+                arrays.push(table.clone());
+                if table == path {
+                    return Some(n + 1);
+                }
+                table.push(index.to_string());
+            }
+            if table == path {
+                return Some(n + 1);
+            }
+        } else if let Some((key, _)) = l.split_once('=') {
+            let mut full = table.clone();
+            full.extend(split(key));
+            if full == path || (full.len() > path.len() && full.starts_with(path)) {
+                return Some(n + 1);
+            }
+            if path.len() > full.len()
+                && path.starts_with(&full)
+                && path[full.len()].parse::<usize>().is_ok()
+            {
+                return Some(n + 1);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unknown_keys(text: &str) -> Vec<(Vec<String>, Option<String>)> {
+        let root = schema();
+        let value: toml::Value = toml::from_str(text).unwrap();
+        let mut unknown = vec![];
+        check_keys(
+            &value,
+            &root.schema,
+            &root.definitions,
+            &mut vec![],
+            &mut unknown,
+        );
+        unknown
+    }
+
+    #[test]
+    fn misspelled_key_in_override() {
+        let text = r#"
+[quality_evaluation_cfg.measure_cfg]
+large_cyclomatic_complexity = 10
+
+[[quality_evaluation_cfg.measure_cfg.overrides]]
+path = "tests/**"
+large_cyclomatic_complexity = 20
+
+[[quality_evaluation_cfg.measure_cfg.overrides]]
+path = "benches/**"
+large_cyclomatc_complexity = 30
+"#;
+        let unknown = unknown_keys(text);
+        assert_eq!(unknown.len(), 1);
+        let (path, suggestion) = &unknown[0];
+        assert_eq!(
+            key_name(path),
+            "quality_evaluation_cfg.measure_cfg.overrides[1].large_cyclomatc_complexity"
+        );
+        assert_eq!(suggestion.as_deref(), Some("large_cyclomatic_complexity"));
+        assert_eq!(find_line(text, path), Some(11));
+    }
+
+    #[test]
+    fn misspelled_key_in_inline_override() {
+        let text = r#"
+[quality_evaluation_cfg.measure_cfg]
+overrides = [{ path = "tests/**", large_cyclomatc_complexity = 20 }]
+"#;
+        let unknown = unknown_keys(text);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(find_line(text, &unknown[0].0), Some(3));
+    }
+
+    #[test]
+    fn known_override_keys() {
+        let text = r#"
+[[quality_evaluation_cfg.measure_cfg.overrides]]
+path = "tests/**"
+large_cyclomatic_complexity = 20
+"#;
+        assert!(unknown_keys(text).is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use cargo::{core, ops, util};
+use krates::{cm, Builder};
+use log;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// A target of a workspace member, e.g. `lib`, `bin`, `test` or `custom-build`.
+#[derive(Debug, Clone)]
+pub struct WorkspaceTarget {
+    pub package: String,
+    /// Directory of the package manifest.
+    pub package_dir: PathBuf,
+    pub target: cm::Target,
+}
+
+impl WorkspaceTarget {
+    /// Root source file of the target, e.g. `src/lib.rs`.
+    pub fn src_path(&self) -> PathBuf {
+        self.target.src_path.clone().into_std_path_buf()
+    }
+}
+
+/// The resolved dependencies of the workspace.
+#[derive(Debug)]
+pub struct Dependencies {
+    pub metadata: cm::Metadata,
+    pub krates: cargo_deny::Krates,
+}
+
+/// Information about the project shared by all check items.
+/// It is built once in `Project::new`, so every check sees the same dependency graph.
+/// The metadata is read without resolving the dependencies, which may need the network; they
+/// are resolved by the first check item asking for them, e.g. the license check.
+#[derive(Debug)]
+pub struct ProjectContext {
+    /// Metadata of the workspace members only.
+    pub metadata: cm::Metadata,
+    manifest: PathBuf,
+    dependencies: Mutex<Option<Arc<Dependencies>>>,
+    pub workspace_members: Vec<cm::Package>,
+    pub targets: Vec<WorkspaceTarget>,
+    /// Changes since the ref of `check --since`, findings outside them are not scored.
+    pub changes: Option<crate::changes::Changes>,
+}
+
+impl ProjectContext {
+    pub fn new(manifest: PathBuf) -> Result<ProjectContext> {
+        let metadata = get_metadata(metadata_options(manifest.clone(), true))?;
+
+        let workspace_members: Vec<cm::Package> = metadata
+            .packages
+            .iter()
+            .filter(|p| metadata.workspace_members.contains(&p.id))
+            .cloned()
+            .collect();
+        let targets = workspace_members
+            .iter()
+            .flat_map(|p| {
+                let package_dir = p
+                    .manifest_path
+                    .parent()
+                    .map(|d| d.to_path_buf().into_std_path_buf())
+                    .unwrap_or_default();
+                p.targets.iter().map(move |t| WorkspaceTarget {
+                    package: p.name.clone(),
+                    package_dir: package_dir.clone(),
+                    target: t.clone(),
+                })
+            })
+            .collect();
+
+        Ok(ProjectContext {
+            metadata,
+            manifest,
+            dependencies: Mutex::new(None),
+            workspace_members,
+            targets,
+            changes: None,
+        })
+    }
+
+    /// Root directory of the workspace.
+    pub fn workspace_root(&self) -> PathBuf {
+        self.metadata.workspace_root.clone().into_std_path_buf()
+    }
+
+    /// The workspace member with the name.
+    pub fn member(&self, name: &str) -> Option<&cm::Package> {
+        self.workspace_members.iter().find(|p| p.name == name)
+    }
+
+    /// The resolved dependencies, resolved on the first call.
+    /// The lock is not held while resolving, so an item abandoned in the middle of it doesn't
+    /// block the others; items asking at the same time may each resolve, the first one is kept.
+    pub fn dependencies(&self) -> Result<Arc<Dependencies>> {
+        if let Some(d) = self.lock_dependencies().as_ref() {
+            return Ok(d.clone());
+        }
+        let metadata = get_metadata(metadata_options(self.manifest.clone(), false))
+            .with_context(|| format!("Load cargo metadata failed! {:?}", self.manifest))?;
+        let krates = gather_krates(metadata.clone()).context("failed to gather crates")?;
+        let d = Arc::new(Dependencies { metadata, krates });
+        Ok(self.lock_dependencies().get_or_insert(d).clone())
+    }
+
+    /// Lock the resolved dependencies, which stay valid if a holder panicked.
+    fn lock_dependencies(&self) -> MutexGuard<'_, Option<Arc<Dependencies>>> {
+        self.dependencies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Ids of the member and all its transitive dependencies.
+    pub fn dependencies_of(&self, name: &str) -> Result<HashSet<cm::PackageId>> {
+        let mut deps = HashSet::new();
+        let dependencies = self.dependencies()?;
+        let (member, resolve) = match (self.member(name), &dependencies.metadata.resolve) {
+            (Some(m), Some(r)) => (m, r),
+            _ => return Ok(deps),
+        };
+        let mut pending = vec![member.id.clone()];
+        while let Some(id) = pending.pop() {
+            if !deps.insert(id.clone()) {
+                continue;
+            }
+            if let Some(node) = resolve.nodes.iter().find(|n| n.id == id) {
+                pending.extend(node.dependencies.iter().cloned());
+            }
+        }
+        Ok(deps)
+    }
+}
+
+/// Options of the metadata of the workspace, with or without its dependencies.
+fn metadata_options(manifest_path: PathBuf, no_deps: bool) -> MetadataOptions {
+    MetadataOptions {
+        no_default_features: true,
+        all_features: true,
+        features: vec![],
+        manifest_path,
+        frozen: false,
+        locked: false,
+        offline: false,
+        no_deps,
+    }
+}
+
+// fixme: copy and edit from cargo-deny.
+struct MetadataOptions {
+    no_default_features: bool,
+    all_features: bool,
+    features: Vec<String>,
+    manifest_path: PathBuf,
+    frozen: bool,
+    locked: bool,
+    offline: bool,
+    no_deps: bool,
+}
+
+// fixme: copy and edit from cargo-deny.
+fn get_metadata(opts: MetadataOptions) -> Result<cm::Metadata, anyhow::Error> {
+    let mut config = util::Config::default()?;
+
+    config.configure(
+        0,
+        true,
+        None,
+        opts.frozen,
+        opts.locked,
+        opts.offline,
+        &None,
+        &[],
+        &[],
+    )?;
+
+    let mut manifest_path = opts.manifest_path;
+
+    if !manifest_path.is_absolute() {
+        manifest_path = std::env::current_dir()
+            .context("unable to determine current directory")?
+            .join(manifest_path);
+    }
+
+    let features = std::rc::Rc::new(
+        opts.features
+            .into_iter()
+            .map(|feat| core::FeatureValue::new(util::interning::InternedString::new(&feat)))
+            .collect(),
+    );
+
+    let ws = core::Workspace::new(&manifest_path, &config)?;
+    let options = ops::OutputMetadataOptions {
+        cli_features: core::resolver::features::CliFeatures {
+            features,
+            all_features: opts.all_features,
+            uses_default_features: !opts.no_default_features,
+        },
+        no_deps: opts.no_deps,
+        version: 1,
+        filter_platforms: vec![],
+    };
+
+    let md = ops::output_metadata(&ws, &options)?;
+    let md_value = serde_json::to_value(md)?;
+
+    Ok(serde_json::from_value(md_value)?)
+}
+
+// fixme: copy and edit from cargo-deny.
+fn gather_krates(metadata: cm::Metadata) -> Result<cargo_deny::Krates, anyhow::Error> {
+    let start = std::time::Instant::now();
+
+    let gb = Builder::new();
+    let graph = gb.build_with_metadata(metadata, |filtered: cm::Package| match filtered.source {
+        Some(src) => {
+            if src.is_crates_io() {
+                log::debug!("filtered {} {}", filtered.name, filtered.version);
+            } else {
+                log::debug!("filtered {} {} {}", filtered.name, filtered.version, src);
+            }
+        }
+        None => log::debug!("filtered {} {}", filtered.name, filtered.version),
+    });
+
+    if let Ok(ref krates) = graph {
+        let end = std::time::Instant::now();
+        log::info!(
+            "gathered {} crates in {}ms",
+            krates.len(),
+            (end - start).as_millis()
+        );
+    }
+
+    Ok(graph?)
+}
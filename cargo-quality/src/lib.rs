@@ -2,11 +2,15 @@ extern crate anyhow;
 extern crate simple_logger;
 extern crate walkdir;
 
+pub mod changes;
 pub mod check_item;
 pub mod command;
 pub mod config;
+pub mod context;
 pub mod log;
 pub mod project;
+pub mod report;
 pub mod result;
+pub mod suppress;
 pub mod toolchains;
 pub mod util;
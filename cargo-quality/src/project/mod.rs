@@ -1,20 +1,46 @@
+use crate::changes;
 use crate::check_item;
 use crate::config;
+use crate::context;
+use crate::report;
 use crate::result;
+use crate::util;
 use anyhow::{Context, Result};
 use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+
+/// A workspace member, evaluated as its own unit.
+#[derive(Debug)]
+pub struct Member {
+    pub name: String,
+    /// Directory of the artifacts of the member.
+    pub result_path: PathBuf,
+    pub check_item: Vec<Box<dyn check_item::CheckItem>>,
+    pub result: result::Result,
+}
 
 #[derive(Debug)]
 pub struct Project {
     pub manifest: PathBuf,
     pub result_path: PathBuf,
     pub cfg: config::Config,
-    pub check_item: Vec<Box<dyn check_item::CheckItem>>,
-    pub result: result::Result,
+    pub context: Arc<context::ProjectContext>,
+    pub members: Vec<Member>,
+    pub result: result::WorkspaceResult,
 }
 
 impl Project {
-    pub fn new(project_path: PathBuf, config: &config::Config) -> Result<Project> {
+    /// Create the project, with the workspace members in `packages`, or all of them if empty.
+    /// The report is written to `out_dir`, default `<project>/cargo_quality_evaluation`.
+    /// With `since`, only the findings in the changes since that git ref are scored.
+    pub fn new(
+        project_path: PathBuf,
+        config: &config::Config,
+        packages: &[String],
+        out_dir: Option<PathBuf>,
+        since: Option<String>,
+    ) -> Result<Project> {
         let check_info: Vec<config::QualityEvaluation> = if let Some(v) = &config.check_quality_item
         {
             v.to_vec()
@@ -24,43 +50,220 @@ impl Project {
         let mut manifest = project_path.clone();
         manifest.push("Cargo.toml");
 
-        let mut result_dir_path = PathBuf::new();
-        result_dir_path.push(
-            std::env::current_dir()
-                .with_context(|| format!("Find current path failed! {:?}", result_dir_path))?,
-        );
-        result_dir_path.push(r"cargo_quality_evaluation");
+        let result_dir_path =
+            out_dir.unwrap_or_else(|| project_path.join(r"cargo_quality_evaluation"));
+        report::clean_stale(&result_dir_path)?;
         std::fs::create_dir_all(&result_dir_path)
             .with_context(|| format!("Create result dir path failed! {:?}", result_dir_path))?;
 
-        let project_cfg = config::ProjectInfoConfig {
-            dir: project_path.clone(),
-            manifest: manifest.clone(),
-            result: result_dir_path.clone(),
-        };
+        let mut context = context::ProjectContext::new(manifest.clone())
+            .with_context(|| format!("Load cargo metadata failed! {:?}", manifest))?;
+        if let Some(since) = since {
+            context.changes = Some(changes::Changes::since(&project_path, &since)?);
+        }
+        let context = Arc::new(context);
+
+        for p in packages {
+            anyhow::ensure!(
+                context.member(p).is_some(),
+                "package '{}' is not a member of the workspace",
+                p
+            );
+        }
+
         let mut pr = Project {
             manifest: project_path,
             result_path: result_dir_path.clone(),
             cfg: config.clone(),
-            check_item: vec![],
+            context: context.clone(),
+            members: vec![],
             result: Default::default(),
         };
-        for q in check_info {
-            pr.check_item
-                .push(check_item::make_check(config.clone(), &q, &project_cfg));
+        for m in context.workspace_members.iter() {
+            if !packages.is_empty() && !packages.contains(&m.name) {
+                continue;
+            }
+            let mut member_result = result_dir_path.clone();
+            member_result.push(report::MEMBERS_DIR);
+            member_result.push(&m.name);
+            let project_cfg = config::ProjectInfoConfig {
+                dir: m
+                    .manifest_path
+                    .parent()
+                    .map(|d| d.to_path_buf().into_std_path_buf())
+                    .unwrap_or_default(),
+                manifest: m.manifest_path.clone().into_std_path_buf(),
+                result: member_result,
+                package: Some(m.name.clone()),
+            };
+            let mut member = Member {
+                name: m.name.clone(),
+                result_path: project_cfg.result.clone(),
+                check_item: vec![],
+                result: Default::default(),
+            };
+            for q in check_info.iter() {
+                member.check_item.push(check_item::make_check(
+                    config.clone(),
+                    q,
+                    &project_cfg,
+                    context.clone(),
+                ));
+            }
+            pr.members.push(member);
         }
 
         Ok(pr)
     }
 
-    // fixme: Use multi-threaded to executing task
+    /// Evaluate the workspace members one by one, then aggregate the workspace score.
     pub fn execute(&mut self) -> Result<()> {
-        for i in &mut self.check_item {
-            i.check(&mut self.result)?;
+        for m in self.members.iter_mut() {
+            log::info!("Evaluate workspace member: {}", m.name);
+            execute_member(m, &self.cfg);
+            m.result.count_total_score();
+            self.result.members.insert(m.name.clone(), m.result.clone());
+        }
+        let aggregation = self
+            .cfg
+            .workspace_cfg
+            .as_ref()
+            .and_then(|w| w.aggregation)
+            .unwrap_or_default();
+        self.result.aggregate(aggregation);
+        self.write_report()
+    }
+
+    /// Write the result and the index of all artifacts.
+    fn write_report(&self) -> Result<()> {
+        let mut artifacts = vec![];
+        for m in self.members.iter() {
+            for a in m.result.artifacts.iter() {
+                let mut a = a.clone();
+                a.member.get_or_insert_with(|| m.name.clone());
+                artifacts.push(a);
+            }
         }
-        Ok(())
+        report::write(&self.result_path, &self.result, artifacts)
     }
-    pub fn get_result(&self) -> result::Result {
+
+    pub fn get_result(&self) -> result::WorkspaceResult {
         self.result.clone()
     }
 }
+
+/// Execute the check items of the member one by one.
+/// Each item runs on its own thread, so a hung item can be abandoned when its timeout
+/// elapses or the user presses Ctrl-C. An abandoned item is signalled through its
+/// `util::CancelToken` and stops at its next poll. Such items and failed items are recorded in
+/// `result.check_item_status` instead of aborting the whole evaluation.
+fn execute_member(member: &mut Member, cfg: &config::Config) {
+    for mut i in std::mem::take(&mut member.check_item) {
+        let item = i.item();
+        let start = Instant::now();
+        if util::is_cancelled() {
+            record_status(
+                &mut member.result,
+                item,
+                result::CheckStatus::Cancelled,
+                start,
+                None,
+            );
+            continue;
+        }
+
+        let timeout = cfg.check_item_timeout(&item);
+        let (tx, rx) = mpsc::channel();
+        let mut r = member.result.clone();
+        let token = util::CancelToken::default();
+        let item_token = token.clone();
+        std::thread::spawn(move || {
+            item_token.enter();
+            let res = i.check(&mut r);
+            let _ = tx.send((i, r, res));
+        });
+
+        let outcome = loop {
+            match rx.recv_timeout(util::POLL_INTERVAL) {
+                Ok(v) => break Ok(v),
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    break Err((result::CheckStatus::Failed, "panicked".to_string()))
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+            }
+            if util::is_cancelled() {
+                let e = util::InterruptError::Cancelled;
+                break Err((result::CheckStatus::Cancelled, e.to_string()));
+            }
+            if let Some(t) = timeout.filter(|t| start.elapsed() >= *t) {
+                let e = util::InterruptError::TimedOut(t);
+                break Err((result::CheckStatus::TimedOut, e.to_string()));
+            }
+        };
+
+        match outcome {
+            Ok((i, r, Ok(()))) => {
+                member.result = r;
+                record_status(
+                    &mut member.result,
+                    item,
+                    result::CheckStatus::Success,
+                    start,
+                    None,
+                );
+                member.check_item.push(i);
+            }
+            Ok((i, _, Err(e))) => {
+                log::error!("Check item {:?} failed: {:?}", item, e);
+                let status = match e.downcast_ref::<util::InterruptError>() {
+                    Some(util::InterruptError::TimedOut(_)) => result::CheckStatus::TimedOut,
+                    Some(util::InterruptError::Cancelled) => result::CheckStatus::Cancelled,
+                    None => result::CheckStatus::Failed,
+                };
+                record_status(
+                    &mut member.result,
+                    item,
+                    status,
+                    start,
+                    Some(format!("{:#}", e)),
+                );
+                record_partial_output(member, item);
+                member.check_item.push(i);
+            }
+            Err((status, message)) => {
+                // The worker thread is detached and told to stop, its partial result is dropped.
+                token.cancel();
+                log::error!("Check item {:?} {}", item, message);
+                record_status(&mut member.result, item, status, start, Some(message));
+                record_partial_output(member, item);
+            }
+        }
+    }
+}
+
+/// List the result dir of an item which did not complete, its own artifacts being lost with
+/// its result, so the files it wrote are indexed and removed by the next run.
+fn record_partial_output(member: &mut Member, item: config::QualityEvaluation) {
+    member.result.artifacts.push(result::Artifact {
+        path: check_item::make_result_path(&item, &member.result_path),
+        check_item: Some(item.name().to_string()),
+        member: Some(member.name.clone()),
+        format: result::ArtifactFormat::Directory,
+        purpose: "Partial output of the check item, which did not complete".to_string(),
+    });
+}
+
+fn record_status(
+    r: &mut result::Result,
+    item: config::QualityEvaluation,
+    status: result::CheckStatus,
+    start: Instant,
+    message: Option<String>,
+) {
+    r.check_item_status.push(result::CheckItemStatus {
+        name: item.name().to_string(),
+        status,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        message,
+    });
+}
@@ -0,0 +1,100 @@
+// Report of an evaluation, see "Report layout" in README.md.
+// Artifacts listed in the `index.json` of a previous run are removed before a new run.
+
+use crate::result;
+use anyhow::{Context, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const INDEX_FILE: &str = "index.json";
+pub const RESULT_FILE: &str = "result.json";
+pub const MEMBERS_DIR: &str = "members";
+
+/// Version of the index format.
+const INDEX_VERSION: u32 = 1;
+
+/// Manifest of all artifacts of a run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Index {
+    pub version: u32,
+    pub artifacts: Vec<result::Artifact>,
+}
+
+/// Remove the artifacts of the previous run, found from its index.
+pub fn clean_stale(out_dir: &Path) -> Result<()> {
+    let index_path = out_dir.join(INDEX_FILE);
+    let content = match std::fs::read(&index_path) {
+        Ok(c) => c,
+        Err(_) => return Ok(()),
+    };
+    let index: Index = match serde_json::from_slice(&content) {
+        Ok(i) => i,
+        Err(e) => {
+            log::warn!("Ignore the invalid index {:?}: {:?}", index_path, e);
+            return Ok(());
+        }
+    };
+    for a in index.artifacts.iter() {
+        // Never follow an index outside of the result directory.
+        if a.path.is_absolute() || a.path.components().any(|c| c.as_os_str() == "..") {
+            continue;
+        }
+        let p = out_dir.join(&a.path);
+        let removed = if p.is_dir() {
+            std::fs::remove_dir_all(&p)
+        } else {
+            std::fs::remove_file(&p)
+        };
+        if let Err(e) = removed {
+            log::debug!("Remove stale artifact {:?} failed: {:?}", p, e);
+        }
+    }
+    remove_empty_dirs(&out_dir.join(MEMBERS_DIR));
+    std::fs::remove_file(&index_path)
+        .with_context(|| format!("Remove stale index failed! {:?}", index_path))
+}
+
+/// Remove the directories left empty by `clean_stale`.
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for e in entries.flatten() {
+            if e.path().is_dir() {
+                remove_empty_dirs(&e.path());
+            }
+        }
+    }
+    let _ = std::fs::remove_dir(dir);
+}
+
+/// Write `result.json` and `index.json` into the result directory.
+pub fn write(
+    out_dir: &Path,
+    r: &result::WorkspaceResult,
+    artifacts: Vec<result::Artifact>,
+) -> Result<()> {
+    let result_path = out_dir.join(RESULT_FILE);
+    std::fs::write(&result_path, serde_json::to_string_pretty(r)?)
+        .with_context(|| format!("Write result failed! {:?}", result_path))?;
+
+    let mut index = Index {
+        version: INDEX_VERSION,
+        artifacts: vec![result::Artifact {
+            path: PathBuf::from(RESULT_FILE),
+            check_item: None,
+            member: None,
+            format: result::ArtifactFormat::Json,
+            purpose: "Scores of the workspace and each member".to_string(),
+        }],
+    };
+    for mut a in artifacts {
+        a.path = a
+            .path
+            .strip_prefix(out_dir)
+            .map(Path::to_path_buf)
+            .unwrap_or(a.path);
+        index.artifacts.push(a);
+    }
+    let index_path = out_dir.join(INDEX_FILE);
+    std::fs::write(&index_path, serde_json::to_string_pretty(&index)?)
+        .with_context(|| format!("Write index failed! {:?}", index_path))
+}
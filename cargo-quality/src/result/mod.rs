@@ -1,5 +1,9 @@
+use crate::config;
+use crate::toolchains::rust_code_analysis::FuncInfo;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub enum CheckResultDetail {
@@ -41,6 +45,14 @@ pub enum CountResultDetail {
         score: Option<u64>,
         normalized_score: Option<u64>,
     },
+    Duplication {
+        score: Option<u64>,
+        normalized_score: Option<u64>,
+    },
+    Doc {
+        score: Option<u64>,
+        normalized_score: Option<u64>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -53,12 +65,174 @@ pub struct StaticCheck {
 pub struct CodeMeasure {
     pub score: Option<u64>,
     pub normalized_score: Option<u64>,
+    pub excluded_files: Option<ExcludedFiles>,
+    /// Source lines of the measured files.
+    pub sloc: Option<u64>,
+    /// Lines of the measured files, by kind.
+    pub loc: Option<LocSummary>,
+    /// How the score is reached: the penalty of each metric.
+    pub penalties: Option<Vec<MetricPenalty>>,
+    /// Findings and penalty of each class of code, e.g. tests.
+    pub classes: Option<Vec<ClassMeasure>>,
+    /// Distribution of each metric over the measured functions or files.
+    pub distributions: Option<Vec<MetricDistribution>>,
+    /// Functions and files furthest past their thresholds, the worst first.
+    pub hotspots: Option<Vec<Hotspot>>,
+    /// Aggregates of each module, the one with the highest hotspot score first.
+    pub modules: Option<Vec<ModuleMeasure>>,
+}
+
+/// Distribution of a metric, over the functions or, for the file metrics, the files.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricDistribution {
+    pub metric: String,
+    pub count: u64,
+    pub mean: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+    /// Number of values in each power of two range, up to the max.
+    pub histogram: Vec<HistogramBucket>,
+}
+
+/// Values greater than the `le` of the previous bucket and at most `le`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistogramBucket {
+    pub le: f64,
+    pub count: u64,
+}
+
+/// A function or file of the hotspot list.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Hotspot {
+    /// Qualified name of the function, or path of the file.
+    pub name: String,
+    /// Relative to the package dir.
+    pub file: PathBuf,
+    pub line: usize,
+    pub class: config::CodeClass,
+    /// Sum of the excess `value / threshold - 1` over the metrics past their threshold.
+    pub score: f64,
+    /// Metrics past their threshold.
+    pub exceeded: Vec<String>,
+}
+
+/// Aggregates of the functions and files of a module.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ModuleMeasure {
+    /// e.g. `my_crate::parser`.
+    pub module: String,
+    pub files: u64,
+    pub functions: u64,
+    /// Lines of the files of the module, counted as `size_measure`.
+    pub lines: u64,
+    /// Functions and files past a threshold.
+    pub findings: u64,
+    pub mean_cyclomatic: f64,
+    pub mean_cognitive: f64,
+    pub max_cognitive: u64,
+    /// Sum of the hotspot scores of its functions and files.
+    pub hotspot_score: f64,
+}
+
+/// Measure of a class of code: production, test, bench, example or build script.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClassMeasure {
+    pub class: config::CodeClass,
+    /// Left out of the score by `measure_cfg.classes.<class>.exclude`.
+    pub excluded: bool,
+    /// Percent of the penalty of its findings charged to the score.
+    pub weight: u64,
+    pub files: u64,
+    pub functions: u64,
+    pub findings: u64,
+    pub penalty: u64,
 }
 
+/// Lines of code of the measured files, from rust-code-analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LocSummary {
+    pub files: u64,
+    pub functions: u64,
+    /// Source lines as rust-code-analysis counts them: every line of the span, comments and
+    /// blank lines included.
+    pub sloc: u64,
+    /// Physical lines of code, without comments and blank lines.
+    pub ploc: u64,
+    /// Logical lines, i.e. statements.
+    pub lloc: u64,
+    /// Comment lines.
+    pub cloc: u64,
+    pub blank: u64,
+}
+
+/// Penalty of a metric of the measure check, explaining the score.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricPenalty {
+    pub metric: String,
+    pub curve: config::PenaltyCurve,
+    /// Penalty of a finding at the threshold.
+    pub score: u64,
+    pub cap: Option<u64>,
+    pub findings: u64,
+    pub penalty: u64,
+}
+
+/// Number of source files left out of the measure, by reason.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ExcludedFiles {
+    pub ignore_file: u64,
+    pub exclude_pattern: u64,
+    pub generated_marker: u64,
+    pub generated_pattern: u64,
+}
+
+/// Token-based clones of the member sources.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct CodeDuplication {
     pub score: Option<u64>,
     pub normalized_score: Option<u64>,
+    /// Fewest tokens of a duplicated block.
+    pub min_tokens: u64,
+    pub files: u64,
+    /// Lines holding a token, `use` declarations and attributes left out.
+    pub lines: u64,
+    /// Lines in a duplicated block.
+    pub duplicated_lines: u64,
+    /// `duplicated_lines` in percent of `lines`.
+    pub percentage: f64,
+    /// Number of duplicated blocks.
+    pub block_count: u64,
+    /// The `top_blocks` largest duplicated blocks.
+    pub blocks: Vec<DuplicatedBlock>,
+    /// Files with duplicated lines, the highest percentage first.
+    pub file_duplication: Vec<FileDuplication>,
+}
+
+/// A block of tokens found twice, renamed identifiers and changed literals aside.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DuplicatedBlock {
+    pub tokens: u64,
+    pub first: CodeSpan,
+    pub second: CodeSpan,
+}
+
+/// Lines of a source file, relative to the package dir.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CodeSpan {
+    pub file: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileDuplication {
+    /// Relative to the package dir.
+    pub file: PathBuf,
+    pub lines: u64,
+    pub duplicated_lines: u64,
+    pub percentage: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -97,10 +271,46 @@ pub struct CompileBuildInfoCheck {
     pub normalized_score: Option<u64>,
 }
 
+/// Documentation of the public items of the library target.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct DocCheck {
     pub score: Option<u64>,
     pub normalized_score: Option<u64>,
+    /// Public items, `#[doc(hidden)]` ones left out.
+    pub items: u64,
+    /// Public items with a doc comment.
+    pub documented: u64,
+    /// `documented` in percent of `items`.
+    pub coverage: f64,
+    /// Coverage of each kind of item, e.g. `fn` or `struct`.
+    pub kinds: Vec<DocKindCoverage>,
+    /// Whether the crate root has `//!` docs.
+    pub crate_doc: bool,
+    /// Public functions whose docs have an `# Examples` section.
+    pub functions_with_examples: u64,
+    /// Level of `missing_docs` set in the crate root, e.g. `deny`.
+    pub missing_docs_lint: Option<String>,
+    /// Whether `cargo doc --no-deps` built the docs.
+    pub doc_build: Option<bool>,
+    /// Rustdoc diagnostics of `cargo doc`, by lint.
+    pub doc_warnings: DocWarnings,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DocWarnings {
+    pub broken_intra_doc_links: u64,
+    pub invalid_codeblock_attributes: u64,
+    pub bare_urls: u64,
+    pub private_intra_doc_links: u64,
+    /// The other `rustdoc::` lints, e.g. `invalid_html_tags`.
+    pub other: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocKindCoverage {
+    pub kind: String,
+    pub items: u64,
+    pub documented: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -115,6 +325,114 @@ pub struct ArchitectureDesignCheck {
     pub normalized_score: Option<u64>,
 }
 
+/// Files changed often in the history window whose functions are complex.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ChurnCheck {
+    pub since_days: u64,
+    /// Commits read in the window, without merges.
+    pub commits: u64,
+    /// Rust files changed in the window.
+    pub files: u64,
+    /// Files both high-churn and high-complexity.
+    pub flagged: u64,
+    /// The `top_hotspots` files with the highest score, and every flagged file.
+    pub hotspots: Vec<ChurnHotspot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChurnHotspot {
+    /// Relative to the package dir.
+    pub file: PathBuf,
+    pub commits: u64,
+    pub authors: u64,
+    pub lines_added: u64,
+    pub lines_deleted: u64,
+    /// Cognitive complexity of its functions, summed.
+    pub complexity: u64,
+    /// `commits × complexity`.
+    pub score: u64,
+    pub flagged: bool,
+    /// Qualified names of its most complex functions.
+    pub functions: Vec<String>,
+}
+
+/// Findings in and out of the changes of `check --since`. Only the touched ones are scored.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ChangeScope {
+    /// The ref given to `--since`.
+    pub since: String,
+    /// The commit the working tree was compared with.
+    pub base: String,
+    pub changed_files: u64,
+    /// Findings in the changed lines, by check item.
+    pub touched: BTreeMap<String, u64>,
+    /// Findings in the untouched code, by check item, reported as context.
+    pub untouched: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactFormat {
+    Json,
+    Toml,
+    Text,
+    /// A directory of files in various formats.
+    Directory,
+}
+
+/// A file or directory written by a check item, listed in `index.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub check_item: Option<String>,
+    pub member: Option<String>,
+    pub format: ArtifactFormat,
+    pub purpose: String,
+}
+
+/// Final state of a check item.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Success,
+    Failed,
+    TimedOut,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckItemStatus {
+    pub name: String,
+    pub status: CheckStatus,
+    pub elapsed_ms: u64,
+    pub message: Option<String>,
+}
+
+/// What a suppression comment applies to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressionScope {
+    /// `allow-file(...)`, the whole file.
+    File,
+    /// `allow(...)`, the item starting below the comment.
+    Item { start_line: usize, end_line: usize },
+}
+
+/// A `// cargo-quality: allow(...)` comment in the sources, listed for audits.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Suppression {
+    /// Relative to the package dir.
+    pub file: PathBuf,
+    /// Line of the comment.
+    pub line: usize,
+    pub scope: SuppressionScope,
+    /// Metrics, e.g. `cognitive_complexity`, or lints, e.g. `clippy::too_many_arguments`.
+    pub rules: Vec<String>,
+    pub reason: Option<String>,
+    /// Number of findings suppressed, by check item.
+    pub suppressed: BTreeMap<String, u64>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Result {
     pub static_check: Option<StaticCheck>,
@@ -129,6 +447,148 @@ pub struct Result {
     pub doc_check: Option<DocCheck>,
     pub test_check: Option<TestCheck>,
     pub architecture_design_check: Option<ArchitectureDesignCheck>,
+    /// Churn × complexity hotspots, not scored.
+    pub churn: Option<ChurnCheck>,
+    /// Set by `check --since`.
+    pub changes: Option<ChangeScope>,
+    pub check_item_status: Vec<CheckItemStatus>,
+    /// Suppression comments found by the check items.
+    pub suppressions: Vec<Suppression>,
+    /// Suppressions without a `reason`.
+    pub unjustified_suppressions: u64,
+    /// Sum of the normalized scores of all check items.
+    pub total_score: Option<u64>,
+    /// Listed in `index.json` instead.
+    #[serde(skip)]
+    pub artifacts: Vec<Artifact>,
+    /// Functions and files of the measure check, reused by the churn check.
+    #[serde(skip)]
+    pub measured: Option<Arc<Vec<FuncInfo>>>,
+}
+
+impl Result {
+    /// Add the suppressions seen by a check item, merging the ones already listed.
+    pub fn add_suppressions(&mut self, suppressions: Vec<Suppression>) {
+        for s in suppressions {
+            match self
+                .suppressions
+                .iter_mut()
+                .find(|e| e.file == s.file && e.line == s.line)
+            {
+                Some(e) => {
+                    for (k, v) in s.suppressed {
+                        *e.suppressed.entry(k).or_default() += v;
+                    }
+                }
+                None => self.suppressions.push(s),
+            }
+        }
+        self.suppressions
+            .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        self.unjustified_suppressions = self
+            .suppressions
+            .iter()
+            .filter(|s| s.reason.is_none())
+            .count() as u64;
+    }
+
+    /// Record the findings of a check item in and out of the changes.
+    pub fn add_changes(
+        &mut self,
+        changes: &crate::changes::Changes,
+        check_item: config::QualityEvaluation,
+        touched: u64,
+        untouched: u64,
+    ) {
+        let scope = self.changes.get_or_insert_with(|| ChangeScope {
+            since: changes.since.clone(),
+            base: changes.base.clone(),
+            changed_files: changes.file_count(),
+            ..Default::default()
+        });
+        let name = check_item.name().to_string();
+        *scope.touched.entry(name.clone()).or_default() += touched;
+        *scope.untouched.entry(name).or_default() += untouched;
+    }
+
+    /// Sum the normalized scores of the check items which have one.
+    pub fn count_total_score(&mut self) {
+        let scores = [
+            self.static_check.as_ref().and_then(|r| r.normalized_score),
+            self.code_measure.as_ref().and_then(|r| r.normalized_score),
+            self.code_duplication
+                .as_ref()
+                .and_then(|r| r.normalized_score),
+            self.dependent_crate
+                .as_ref()
+                .and_then(|r| r.normalized_score),
+            self.security_check
+                .as_ref()
+                .and_then(|r| r.normalized_score),
+            self.dynamic_check.as_ref().and_then(|r| r.normalized_score),
+            self.vulnerability_scan
+                .as_ref()
+                .and_then(|r| r.normalized_score),
+            self.license_check.as_ref().and_then(|r| r.normalized_score),
+            self.compile_build_info_check
+                .as_ref()
+                .and_then(|r| r.normalized_score),
+            self.doc_check.as_ref().and_then(|r| r.normalized_score),
+            self.test_check.as_ref().and_then(|r| r.normalized_score),
+            self.architecture_design_check
+                .as_ref()
+                .and_then(|r| r.normalized_score),
+        ];
+        self.total_score = scores.iter().flatten().copied().reduce(|a, b| a + b);
+    }
+}
+
+/// Result of each workspace member and the aggregated workspace score.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WorkspaceResult {
+    /// Where the config of the evaluation came from.
+    pub config_source: Option<config::ConfigSource>,
+    pub score: Option<u64>,
+    pub aggregation: Option<config::Aggregation>,
+    pub members: BTreeMap<String, Result>,
+}
+
+impl WorkspaceResult {
+    /// Aggregate the total scores of the members.
+    /// Falls back to the simple average when the SLOC of a member is unknown.
+    pub fn aggregate(&mut self, aggregation: config::Aggregation) {
+        let scored: Vec<(u64, Option<u64>)> = self
+            .members
+            .values()
+            .filter_map(|r| {
+                let sloc = r.code_measure.as_ref().and_then(|m| m.sloc);
+                r.total_score.map(|s| (s, sloc))
+            })
+            .collect();
+        if scored.is_empty() {
+            return;
+        }
+        let mut aggregation = aggregation;
+        if aggregation == config::Aggregation::SlocWeighted
+            && scored.iter().any(|(_, sloc)| sloc.is_none())
+        {
+            log::warn!("SLOC of some members is unknown, aggregate by simple average.");
+            aggregation = config::Aggregation::Average;
+        }
+        let (sum, weight) = scored.iter().fold((0, 0), |(sum, weight), (s, sloc)| {
+            let w = match aggregation {
+                config::Aggregation::SlocWeighted => sloc.unwrap_or_default(),
+                config::Aggregation::Average => 1,
+            };
+            (sum + s * w, weight + w)
+        });
+        self.aggregation = Some(aggregation);
+        self.score = if weight == 0 {
+            None
+        } else {
+            Some(sum / weight)
+        };
+    }
 }
 
 pub trait ResultInfo {
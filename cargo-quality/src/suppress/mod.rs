@@ -0,0 +1,381 @@
+// In-source suppressions of quality findings:
+//
+//     // cargo-quality: allow(cognitive_complexity, reason = "state machine of the parser")
+//     fn parse(...) { ... }
+//
+//     // cargo-quality: allow-file(num_rows_file, reason = "generated tables")
+//
+// `allow` applies to the item below the comment, skipping doc comments and attributes, or to
+// the statement below it in a function.
+// `allow-file` applies to the whole file.
+
+use crate::result;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+
+const MARKER: &str = "cargo-quality:";
+
+/// Rules of the measure check, named after the `large_*` and `small_*` thresholds.
+pub const CYCLOMATIC_COMPLEXITY: &str = "cyclomatic_complexity";
+pub const COGNITIVE_COMPLEXITY: &str = "cognitive_complexity";
+pub const NUM_ROWS_FUNCTION: &str = "num_rows_function";
+pub const NUM_ROWS_FILE: &str = "num_rows_file";
+pub const HALSTEAD_VOLUME: &str = "halstead_volume";
+pub const MAINTAINABILITY_INDEX: &str = "maintainability_index";
+pub const ABC: &str = "abc";
+pub const NOM: &str = "nom";
+pub const NARGS: &str = "nargs";
+pub const NEXITS: &str = "nexits";
+
+/// All the rules of the measure check.
+pub const MEASURE_METRICS: &[&str] = &[
+    CYCLOMATIC_COMPLEXITY,
+    COGNITIVE_COMPLEXITY,
+    NUM_ROWS_FUNCTION,
+    NUM_ROWS_FILE,
+    HALSTEAD_VOLUME,
+    MAINTAINABILITY_INDEX,
+    ABC,
+    NOM,
+    NARGS,
+    NEXITS,
+];
+
+/// The suppressions of the source files, scanned once per file.
+#[derive(Debug, Default)]
+pub struct Suppressions {
+    /// Package dir, the suppressed files are reported relative to it.
+    root: PathBuf,
+    files: HashMap<PathBuf, Vec<result::Suppression>>,
+}
+
+impl Suppressions {
+    pub fn new(root: &Path) -> Suppressions {
+        Suppressions {
+            root: root.to_path_buf(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Find the suppression of `rule` for the finding at `line` of `file`, and count it
+    /// for `check_item`. Without a line, only `allow-file` applies.
+    pub fn suppress(
+        &mut self,
+        file: &Path,
+        line: Option<usize>,
+        rule: &str,
+        check_item: &str,
+    ) -> bool {
+        let root = &self.root;
+        let list = self
+            .files
+            .entry(file.to_path_buf())
+            .or_insert_with(|| scan(file, root));
+        let found = list.iter_mut().find(|s| {
+            let in_scope = match (s.scope, line) {
+                (result::SuppressionScope::File, _) => true,
+                (
+                    result::SuppressionScope::Item {
+                        start_line,
+                        end_line,
+                    },
+                    Some(l),
+                ) => start_line <= l && l <= end_line,
+                (result::SuppressionScope::Item { .. }, None) => false,
+            };
+            in_scope && s.rules.iter().any(|r| r == rule)
+        });
+        match found {
+            Some(s) => {
+                *s.suppressed.entry(check_item.to_string()).or_default() += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Scan the file even if it has no finding, so its suppressions are listed.
+    pub fn scan_file(&mut self, file: &Path) {
+        let root = &self.root;
+        self.files
+            .entry(file.to_path_buf())
+            .or_insert_with(|| scan(file, root));
+    }
+
+    /// All the suppressions seen, warning about the ones without a reason.
+    pub fn into_list(self) -> Vec<result::Suppression> {
+        let mut list: Vec<result::Suppression> = self.files.into_values().flatten().collect();
+        list.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        for s in list.iter().filter(|s| s.reason.is_none()) {
+            log::warn!(
+                "Suppression without a reason at {}:{}, add `reason = \"...\"`",
+                s.file.display(),
+                s.line
+            );
+        }
+        list
+    }
+}
+
+/// Read the suppression comments of the file.
+fn scan(file: &Path, root: &Path) -> Vec<result::Suppression> {
+    let text = match std::fs::read_to_string(file) {
+        Ok(t) => t,
+        Err(e) => {
+            log::debug!("Read {:?} for suppressions failed: {:?}", file, e);
+            return vec![];
+        }
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let comments: Vec<(usize, (bool, Vec<String>, Option<String>))> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, l)| parse_comment(l).map(|c| (i, c)))
+        .collect();
+    // The file is only parsed when an item is suppressed.
+    let spans = if comments.iter().any(|(_, (file_scope, _, _))| !file_scope) {
+        match syn::parse_file(&text) {
+            Ok(f) => item_spans(&f),
+            Err(e) => {
+                log::warn!("Parse {:?} for suppressions failed: {:?}", file, e);
+                vec![]
+            }
+        }
+    } else {
+        vec![]
+    };
+    let mut out = vec![];
+    for (i, (file_scope, rules, reason)) in comments {
+        let scope = if file_scope {
+            result::SuppressionScope::File
+        } else {
+            match item_span(&lines, &spans, i + 1) {
+                Some((start_line, end_line)) => result::SuppressionScope::Item {
+                    start_line,
+                    end_line,
+                },
+                None => continue,
+            }
+        };
+        out.push(result::Suppression {
+            file: file.strip_prefix(root).unwrap_or(file).to_path_buf(),
+            line: i + 1,
+            scope,
+            rules,
+            reason,
+            suppressed: BTreeMap::new(),
+        });
+    }
+    out
+}
+
+/// Parse `// cargo-quality: allow(rule, ..., reason = "...")`.
+/// Returns whether it is `allow-file`, the rules and the reason.
+fn parse_comment(line: &str) -> Option<(bool, Vec<String>, Option<String>)> {
+    let body = line.trim_start().strip_prefix("//")?;
+    let body = body.trim_start_matches(['/', '!']).trim_start();
+    let body = body.strip_prefix(MARKER)?.trim_start();
+    let (file_scope, body) = if let Some(b) = body.strip_prefix("allow-file") {
+        (true, b)
+    } else {
+        (false, body.strip_prefix("allow")?)
+    };
+    let body = body.trim_start().strip_prefix('(')?;
+    let body = &body[..body.rfind(')')?];
+
+    let mut rules = vec![];
+    let mut reason = None;
+    for arg in split_args(body) {
+        match arg.split_once('=') {
+            Some((k, v)) if k.trim() == "reason" => {
+                let v = v.trim().trim_matches('"').trim();
+                if !v.is_empty() {
+                    reason = Some(v.to_string());
+                }
+            }
+            _ if !arg.is_empty() => rules.push(arg.to_string()),
+            _ => {}
+        }
+    }
+    Some((file_scope, rules, reason))
+}
+
+/// Split the arguments at the commas outside of quotes.
+fn split_args(s: &str) -> Vec<&str> {
+    let mut out = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                out.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(s[start..].trim());
+    out
+}
+
+/// 1-based span of the item or statement below the comment at the 1-based `line`, found
+/// in `spans`. Doc comments and attributes of the item may be on either side of the comment.
+fn item_span(lines: &[&str], spans: &[(usize, usize)], line: usize) -> Option<(usize, usize)> {
+    let is_attr = |l: &str| l.is_empty() || l.starts_with("//") || l.starts_with("#[");
+    // The first line of code below the comment.
+    let mut next = line + 1;
+    let mut attr_depth = 0i32;
+    while next <= lines.len() {
+        let l = lines[next - 1].trim();
+        if attr_depth > 0 || l.starts_with("#[") {
+            attr_depth += l.matches('[').count() as i32 - l.matches(']').count() as i32;
+        } else if !is_attr(l) {
+            break;
+        }
+        next += 1;
+    }
+    spans
+        .iter()
+        .filter(|(start, end)| *start <= next && next <= *end)
+        // Not an item around the comment, unless the comment is among its attributes.
+        .filter(|(start, _)| {
+            *start > line || lines[*start - 1..line].iter().all(|l| is_attr(l.trim()))
+        })
+        .min_by_key(|(start, end)| (*start, std::cmp::Reverse(*end)))
+        .copied()
+}
+
+/// 1-based spans of the items of the file, and of the statements of their functions,
+/// attributes included.
+fn item_spans(file: &syn::File) -> Vec<(usize, usize)> {
+    let mut out = vec![];
+    collect_items(&file.items, &mut out);
+    out
+}
+
+fn push_span(node: &impl Spanned, out: &mut Vec<(usize, usize)>) {
+    let span = node.span();
+    out.push((span.start().line, span.end().line));
+}
+
+fn collect_items(items: &[syn::Item], out: &mut Vec<(usize, usize)>) {
+    for item in items {
+        push_span(item, out);
+        match item {
+            syn::Item::Fn(f) => collect_stmts(&f.block, out),
+            syn::Item::Mod(m) => {
+                if let Some((_, content)) = &m.content {
+                    collect_items(content, out);
+                }
+            }
+            syn::Item::Impl(i) => {
+                for item in i.items.iter() {
+                    push_span(item, out);
+                    if let syn::ImplItem::Method(m) = item {
+                        collect_stmts(&m.block, out);
+                    }
+                }
+            }
+            syn::Item::Trait(t) => {
+                for item in t.items.iter() {
+                    push_span(item, out);
+                    if let syn::TraitItem::Method(syn::TraitItemMethod {
+                        default: Some(block),
+                        ..
+                    }) = item
+                    {
+                        collect_stmts(block, out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_stmts(block: &syn::Block, out: &mut Vec<(usize, usize)>) {
+    for stmt in block.stmts.iter() {
+        match stmt {
+            syn::Stmt::Item(item) => collect_items(std::slice::from_ref(item), out),
+            _ => push_span(stmt, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans_of(text: &str) -> Vec<Option<(usize, usize)>> {
+        let lines: Vec<&str> = text.lines().collect();
+        let spans = item_spans(&syn::parse_file(text).unwrap());
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| matches!(parse_comment(l), Some((false, _, _))))
+            .map(|(i, _)| item_span(&lines, &spans, i + 1))
+            .collect()
+    }
+
+    #[test]
+    fn item_below() {
+        let text = r#"
+/// Doc of f.
+// cargo-quality: allow(cyclomatic_complexity, reason = "table")
+#[inline]
+fn f() {
+    g();
+}
+
+fn h() {}
+"#;
+        assert_eq!(spans_of(text), vec![Some((2, 7))]);
+    }
+
+    #[test]
+    fn braces_in_literals() {
+        let text = r#"
+// cargo-quality: allow(num_rows_function)
+fn f() -> char {
+    let s = "{ not a block";
+    let r = r"}}";
+    /* } */
+    '{'
+}
+
+fn g() {}
+"#;
+        assert_eq!(spans_of(text), vec![Some((3, 8))]);
+    }
+
+    #[test]
+    fn closing_brace_in_string() {
+        let text = r#"
+// cargo-quality: allow(num_rows_function)
+fn f() {
+    println!("}");
+    g();
+}
+"#;
+        assert_eq!(spans_of(text), vec![Some((3, 6))]);
+    }
+
+    #[test]
+    fn method_and_statement() {
+        let text = r#"
+impl S {
+    // cargo-quality: allow(nargs)
+    fn f(&self, a: u8, b: u8) {
+        // cargo-quality: allow(cognitive_complexity)
+        let c = |x: u8| {
+            x + 1
+        };
+        c(a);
+    }
+}
+"#;
+        assert_eq!(spans_of(text), vec![Some((4, 10)), Some((6, 8))]);
+    }
+}
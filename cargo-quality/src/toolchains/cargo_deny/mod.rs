@@ -1,16 +1,15 @@
 use crate::config;
+use crate::context;
 use crate::result;
 use anyhow::{Context, Result};
-use cargo::{core, ops, util};
 use cargo_deny::{diag::Files, licenses, licenses::LicenseStore, Kid};
-use krates::Builder;
 use licenses::LicenseInfo;
 use log;
 use serde::Serialize;
 use std::collections::{BTreeMap, HashSet};
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::sync::Arc;
 
 /// loading license store
 pub(crate) fn load_license_store() -> Result<LicenseStore, anyhow::Error> {
@@ -33,6 +32,7 @@ pub struct CrateLicenseInfo {
 pub struct LicenseCheck {
     pub project_cfg: config::ProjectInfoConfig,
     pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
     pub crate_licenses: BTreeMap<Kid, Vec<String>>,
     pub license_crates: Vec<(String, Vec<Kid>)>,
     pub unlicense_crates: Vec<Kid>,
@@ -48,13 +48,9 @@ impl super::CheckToolOption for LicenseCheck {
     /// cargo-deny's subcommand 'cargo deny list'
     fn check(&mut self) -> Result<()> {
         log::info!("LicenseCheck check: {:?}", self.project_cfg);
-        let (krates, store) = rayon::join(
-            || gather_krates(self.project_cfg.manifest.clone()),
-            load_license_store,
-        );
-
-        let krates = krates.context("failed to gather crates")?;
-        let store = store.context("failed to load license store")?;
+        let dependencies = self.context.dependencies()?;
+        let krates = &dependencies.krates;
+        let store = load_license_store().context("failed to load license store")?;
 
         let gatherer = licenses::Gatherer::default()
             .with_store(std::sync::Arc::new(store))
@@ -62,12 +58,24 @@ impl super::CheckToolOption for LicenseCheck {
 
         let mut files = Files::new();
 
-        let summary = gatherer.gather(&krates, &mut files, None);
+        let summary = gatherer.gather(krates, &mut files, None);
 
         let licenses = &mut self.license_crates;
         let unlicensed = &mut self.unlicense_crates;
+        // Only the dependencies of the workspace member under evaluation.
+        let scope = self
+            .project_cfg
+            .package
+            .as_ref()
+            .map(|p| self.context.dependencies_of(p))
+            .transpose()?;
 
         for krate_lic_nfo in summary.nfos {
+            if let Some(scope) = &scope {
+                if !scope.contains(&krate_lic_nfo.krate.id) {
+                    continue;
+                }
+            }
             let mut cur = Vec::with_capacity(2);
 
             match krate_lic_nfo.lic_info {
@@ -208,6 +216,13 @@ impl super::CheckToolOption for LicenseCheck {
     /// Process the results presented to the user
     fn result(&mut self, result: &mut result::Result) -> Result<()> {
         log::info!("LicenseCheck result: {:?}", self.count_result);
+        result.artifacts.push(result::Artifact {
+            path: self.project_cfg.result.clone(),
+            check_item: Some(config::QualityEvaluation::License.name().to_string()),
+            member: self.project_cfg.package.clone(),
+            format: result::ArtifactFormat::Text,
+            purpose: "Licenses of each dependency crate".to_string(),
+        });
         if let Some(result::CountResultDetail::CargoLicense {
             score: Some(score),
             normalized_score: Some(n_score),
@@ -237,102 +252,3 @@ impl LicenseCheck {
         Ok(())
     }
 }
-
-// fixme: copy and edit from cargo-deny.
-struct MetadataOptions {
-    no_default_features: bool,
-    all_features: bool,
-    features: Vec<String>,
-    manifest_path: PathBuf,
-    frozen: bool,
-    locked: bool,
-    offline: bool,
-}
-
-// fixme: copy and edit from cargo-deny.
-fn get_metadata(opts: MetadataOptions) -> Result<krates::cm::Metadata, anyhow::Error> {
-    let mut config = util::Config::default()?;
-
-    config.configure(
-        0,
-        true,
-        None,
-        opts.frozen,
-        opts.locked,
-        opts.offline,
-        &None,
-        &[],
-        &[],
-    )?;
-
-    let mut manifest_path = opts.manifest_path;
-
-    if !manifest_path.is_absolute() {
-        manifest_path = std::env::current_dir()
-            .context("unable to determine current directory")?
-            .join(manifest_path);
-    }
-
-    let features = std::rc::Rc::new(
-        opts.features
-            .into_iter()
-            .map(|feat| core::FeatureValue::new(util::interning::InternedString::new(&feat)))
-            .collect(),
-    );
-
-    let ws = core::Workspace::new(&manifest_path, &config)?;
-    let options = ops::OutputMetadataOptions {
-        cli_features: core::resolver::features::CliFeatures {
-            features,
-            all_features: opts.all_features,
-            uses_default_features: !opts.no_default_features,
-        },
-        no_deps: false,
-        version: 1,
-        filter_platforms: vec![],
-    };
-
-    let md = ops::output_metadata(&ws, &options)?;
-    let md_value = serde_json::to_value(md)?;
-
-    Ok(serde_json::from_value(md_value)?)
-}
-
-// fixme: copy and edit from cargo-deny.
-pub fn gather_krates(manifest: PathBuf) -> Result<cargo_deny::Krates, anyhow::Error> {
-    let start = std::time::Instant::now();
-    let metadata = get_metadata(MetadataOptions {
-        no_default_features: true,
-        all_features: true,
-        features: vec![],
-        manifest_path: manifest,
-        frozen: false,
-        locked: false,
-        offline: false,
-    })?;
-
-    let gb = Builder::new();
-    let graph = gb.build_with_metadata(metadata, |filtered: krates::cm::Package| {
-        match filtered.source {
-            Some(src) => {
-                if src.is_crates_io() {
-                    log::debug!("filtered {} {}", filtered.name, filtered.version);
-                } else {
-                    log::debug!("filtered {} {} {}", filtered.name, filtered.version, src);
-                }
-            }
-            None => log::debug!("filtered {} {}", filtered.name, filtered.version),
-        }
-    });
-
-    if let Ok(ref krates) = graph {
-        let end = std::time::Instant::now();
-        log::info!(
-            "gathered {} crates in {}ms",
-            krates.len(),
-            (end - start).as_millis()
-        );
-    }
-
-    Ok(graph?)
-}
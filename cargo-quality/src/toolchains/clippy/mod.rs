@@ -1,17 +1,26 @@
 use crate::config;
+use crate::context;
 use crate::result;
+use crate::suppress;
 use crate::util;
 use anyhow::Result;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Clippy check struct
 #[derive(Debug)]
 pub struct Clippy {
     pub project_cfg: config::ProjectInfoConfig,
     pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
     pub lint_info: HashMap<u32, LintInfo>,
+    pub suppressions: Vec<result::Suppression>,
+    /// Lints in and out of the changes of `check --since`.
+    pub touched: u64,
+    pub untouched: u64,
     pub check_result: Option<result::CheckResultDetail>,
     pub parse_result: Option<result::ParseResultDetail>,
     pub count_result: Option<result::CountResultDetail>,
@@ -35,7 +44,63 @@ pub struct LintInfo {
     pub content: Vec<String>,
 }
 
+impl LintInfo {
+    /// Name of the lint, e.g. `clippy::needless_return`.
+    pub fn name(&self) -> Option<&str> {
+        match &self.lint_kind {
+            LintKind::ClippyWarn(s)
+            | LintKind::ClippyDeny(s)
+            | LintKind::RustWarn(s)
+            | LintKind::RustDeny(s) => s.rsplit('(').next(),
+            LintKind::None => None,
+        }
+    }
+
+    /// Primary location of the lint, from the `--> file:line:column` row.
+    pub fn location(&self) -> Option<(PathBuf, usize)> {
+        let l = self
+            .content
+            .iter()
+            .find_map(|l| l.trim_start().strip_prefix("--> "))?;
+        let mut parts = l.trim().rsplitn(3, ':');
+        let _column = parts.next()?;
+        let line = parts.next()?.parse().ok()?;
+        Some((PathBuf::from(parts.next()?), line))
+    }
+}
+
 impl Clippy {
+    /// Whether a suppression comment accepts the lint.
+    fn is_suppressed(&self, lint: &LintInfo, suppressions: &mut suppress::Suppressions) -> bool {
+        let (name, (file, line)) = match (lint.name(), lint.location()) {
+            (Some(n), Some(l)) => (n, l),
+            _ => return false,
+        };
+        // Paths in the output of cargo are relative to the workspace root.
+        let file = self.context.workspace_root().join(file);
+        suppressions.suppress(
+            &file,
+            Some(line),
+            name,
+            config::QualityEvaluation::StaticCheck.name(),
+        )
+    }
+
+    /// Whether the lint is in the changes of `check --since`, always without `--since`.
+    /// A lint without a location is outside of them.
+    fn is_touched(&self, lint: &LintInfo) -> bool {
+        let changes = match &self.context.changes {
+            Some(c) => c,
+            None => return true,
+        };
+        match lint.location() {
+            Some((file, line)) => {
+                changes.touches(&self.context.workspace_root().join(file), line, line)
+            }
+            None => false,
+        }
+    }
+
     /// Save lint item
     fn save_lint_item(&mut self, ind: u32, lint_kind: LintKind, content: Vec<String>) {
         if lint_kind == LintKind::None {
@@ -60,7 +125,12 @@ impl super::CheckToolOption for Clippy {
             &self.project_cfg.manifest,
             &self.project_cfg.result,
             util::StdOutput::Err,
+            self.config.command_timeout(),
         ) {
+            // A killed clippy leaves an incomplete output, don't score it.
+            if e.downcast_ref::<util::InterruptError>().is_some() {
+                return Err(e);
+            }
             log::info!("Clippy check failed: {:?}", e);
         }
         self.check_result = Some(result::CheckResultDetail::Clippy {
@@ -104,7 +174,15 @@ impl super::CheckToolOption for Clippy {
 
         let mut res_err: u64 = 0;
         let mut res_warn: u64 = 0;
+        let mut suppressions = suppress::Suppressions::new(&self.project_cfg.dir);
         for (_, v) in self.lint_info.iter() {
+            if self.is_suppressed(v, &mut suppressions) {
+                continue;
+            }
+            if !self.is_touched(v) {
+                self.untouched += 1;
+                continue;
+            }
             match v.lint_kind {
                 LintKind::ClippyWarn(_) | LintKind::RustWarn(_) => {
                     res_warn += 1;
@@ -115,6 +193,8 @@ impl super::CheckToolOption for Clippy {
                 _ => {}
             }
         }
+        self.suppressions = suppressions.into_list();
+        self.touched = res_err + res_warn;
         self.parse_result = Some(result::ParseResultDetail::Clippy {
             error: Some(res_err),
             warn: Some(res_warn),
@@ -162,6 +242,22 @@ impl super::CheckToolOption for Clippy {
     /// Clippy is currently the only static checking tool
     fn result(&mut self, result: &mut result::Result) -> Result<()> {
         log::info!("Clippy result: {:?}", self.count_result);
+        result.artifacts.push(result::Artifact {
+            path: self.project_cfg.result.clone(),
+            check_item: Some(config::QualityEvaluation::StaticCheck.name().to_string()),
+            member: self.project_cfg.package.clone(),
+            format: result::ArtifactFormat::Text,
+            purpose: "Raw output of `cargo clippy`".to_string(),
+        });
+        result.add_suppressions(std::mem::take(&mut self.suppressions));
+        if let Some(changes) = &self.context.changes {
+            result.add_changes(
+                changes,
+                config::QualityEvaluation::StaticCheck,
+                self.touched,
+                self.untouched,
+            );
+        }
         if let Some(result::CountResultDetail::Clippy {
             score: Some(score),
             normalized_score: Some(n_score),
@@ -0,0 +1,512 @@
+// Documentation coverage of the public API: the public items of the library target, found by
+// parsing its module tree with syn from the crate root.
+//
+// An item is public when it and its enclosing modules are `pub`; `#[macro_export]` macros are
+// public wherever they are declared. Methods of inherent impls count as functions, the items of
+// trait impls are documented by their trait. `#[doc(hidden)]` and `#[cfg(test)]` items are
+// left out. The rustdoc diagnostics of `cargo doc` are scored along with the coverage.
+
+use super::rustdoc::{DocFinding, Rustdoc};
+use super::CheckToolOption;
+use crate::config;
+use crate::context;
+use crate::result;
+use crate::util;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Kinds of the public items, in the order of the result.
+const KINDS: &[&str] = &["fn", "struct", "enum", "trait", "mod", "const", "macro"];
+
+/// Rustdoc lints counted on their own in the result, the others are counted together.
+const DOC_WARNING_LINTS: &[&str] = &[
+    "broken_intra_doc_links",
+    "invalid_codeblock_attributes",
+    "bare_urls",
+    "private_intra_doc_links",
+];
+
+/// Levels of `missing_docs` which make an undocumented item fail the build.
+const ENFORCING_LINT_LEVELS: &[&str] = &["deny", "forbid"];
+
+/// A public item and its documentation.
+#[derive(Debug, Clone, Serialize)]
+pub struct DocItem {
+    pub kind: &'static str,
+    /// e.g. `my_crate::parser::Lexer::next_token`.
+    pub name: String,
+    /// Relative to the package dir.
+    pub file: PathBuf,
+    pub line: usize,
+    pub documented: bool,
+    /// Whether the docs of a function have an `# Examples` section.
+    pub examples: Option<bool>,
+}
+
+/// The detailed result, written to `doc_check.toml`.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    items: &'a [DocItem],
+    findings: &'a [DocFinding],
+}
+
+/// Text of the doc attributes, `None` without any. `#[doc = include_str!(..)]` counts as an
+/// empty doc.
+fn doc_text(attrs: &[syn::Attribute]) -> Option<String> {
+    let mut text: Option<String> = None;
+    for attr in attrs.iter().filter(|a| a.path.is_ident("doc")) {
+        let line = match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(s),
+                ..
+            })) => s.value(),
+            Ok(syn::Meta::List(_)) => continue,
+            _ => String::new(),
+        };
+        let t = text.get_or_insert_with(String::new);
+        t.push_str(&line);
+        t.push('\n');
+    }
+    text
+}
+
+fn has_examples(doc: &str) -> bool {
+    doc.lines().any(|l| l.trim_start().starts_with("# Example"))
+}
+
+/// Whether the attribute is `#[doc(hidden)]`.
+fn is_doc_hidden(attr: &syn::Attribute) -> bool {
+    if !attr.path.is_ident("doc") {
+        return false;
+    }
+    match attr.parse_meta() {
+        Ok(syn::Meta::List(l)) => l.nested.iter().any(|n| match n {
+            syn::NestedMeta::Meta(syn::Meta::Path(p)) => p.is_ident("hidden"),
+            _ => false,
+        }),
+        _ => false,
+    }
+}
+
+/// Whether the item is left out: `#[doc(hidden)]`, or `#[cfg(test)]` and the other
+/// predicates only true in test builds.
+fn is_skipped(attrs: &[syn::Attribute]) -> bool {
+    attrs
+        .iter()
+        .any(|a| is_doc_hidden(a) || util::cfg::is_test_cfg(a))
+}
+
+fn is_pub(vis: &syn::Visibility) -> bool {
+    matches!(vis, syn::Visibility::Public(_))
+}
+
+/// Whether the lint list, e.g. `deny(missing_docs, unsafe_code)`, names `missing_docs`.
+fn names_missing_docs(list: &syn::MetaList) -> bool {
+    list.nested.iter().any(|n| match n {
+        syn::NestedMeta::Meta(syn::Meta::Path(p)) => p.is_ident("missing_docs"),
+        _ => false,
+    })
+}
+
+/// Level of `missing_docs` set by the inner attributes of the crate root, in a `cfg_attr`
+/// too unless it only applies to test builds.
+fn missing_docs_lint(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .flat_map(util::cfg::applied_metas)
+        .filter_map(|m| match m {
+            syn::Meta::List(l) if names_missing_docs(&l) => {
+                l.path.get_ident().map(|i| i.to_string())
+            }
+            _ => None,
+        })
+        .filter(|l| ["allow", "warn", "deny", "forbid"].contains(&l.as_str()))
+        .last()
+}
+
+fn parse_file(file: &Path) -> Result<syn::File> {
+    let text = std::fs::read_to_string(file)
+        .with_context(|| format!("Read source file failed! {:?}", file))?;
+    syn::parse_file(&text).with_context(|| format!("Parse source file failed! {:?}", file))
+}
+
+/// Walks the module tree of a crate and records its public items.
+struct Walker<'a> {
+    package_dir: &'a Path,
+    krate: String,
+    items: Vec<DocItem>,
+    visited: HashSet<PathBuf>,
+}
+
+impl Walker<'_> {
+    fn record(
+        &mut self,
+        kind: &'static str,
+        ident: &syn::Ident,
+        doc: Option<String>,
+        file: &Path,
+        path: &[String],
+    ) {
+        let mut name = vec![self.krate.clone()];
+        name.extend(path.iter().cloned());
+        name.push(ident.to_string());
+        self.items.push(DocItem {
+            kind,
+            name: name.join("::"),
+            file: file
+                .strip_prefix(self.package_dir)
+                .unwrap_or(file)
+                .to_path_buf(),
+            line: ident.span().start().line,
+            documented: doc.is_some(),
+            examples: (kind == "fn").then(|| doc.as_deref().map_or(false, has_examples)),
+        });
+    }
+
+    /// Record the public items of a module, `dir` is where its module files are.
+    fn module(
+        &mut self,
+        file: &Path,
+        items: &[syn::Item],
+        path: &[String],
+        dir: &Path,
+        public: bool,
+    ) {
+        for item in items {
+            let (kind, attrs, vis, ident) = match item {
+                syn::Item::Fn(i) => ("fn", &i.attrs, &i.vis, &i.sig.ident),
+                syn::Item::Struct(i) => ("struct", &i.attrs, &i.vis, &i.ident),
+                syn::Item::Enum(i) => ("enum", &i.attrs, &i.vis, &i.ident),
+                syn::Item::Trait(i) => ("trait", &i.attrs, &i.vis, &i.ident),
+                syn::Item::Const(i) => ("const", &i.attrs, &i.vis, &i.ident),
+                syn::Item::Mod(m) => {
+                    self.submodule(m, file, path, dir, public);
+                    continue;
+                }
+                syn::Item::Impl(i) => {
+                    self.inherent_impl(i, file, path, public);
+                    continue;
+                }
+                // An exported macro is at the crate root.
+                syn::Item::Macro(m) => {
+                    if let Some(ident) = &m.ident {
+                        if m.attrs.iter().any(|a| a.path.is_ident("macro_export"))
+                            && !is_skipped(&m.attrs)
+                        {
+                            self.record("macro", ident, doc_text(&m.attrs), file, &[]);
+                        }
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if public && is_pub(vis) && !is_skipped(attrs) {
+                self.record(kind, ident, doc_text(attrs), file, path);
+            }
+        }
+    }
+
+    /// Record the public methods of an inherent impl.
+    fn inherent_impl(&mut self, i: &syn::ItemImpl, file: &Path, path: &[String], public: bool) {
+        if !public || i.trait_.is_some() || is_skipped(&i.attrs) {
+            return;
+        }
+        let ty = match &*i.self_ty {
+            syn::Type::Path(p) => match p.path.segments.last() {
+                Some(s) => s.ident.to_string(),
+                None => return,
+            },
+            _ => return,
+        };
+        let mut path = path.to_vec();
+        path.push(ty);
+        for item in i.items.iter() {
+            if let syn::ImplItem::Method(m) = item {
+                if is_pub(&m.vis) && !is_skipped(&m.attrs) {
+                    self.record("fn", &m.sig.ident, doc_text(&m.attrs), file, &path);
+                }
+            }
+        }
+    }
+
+    /// Record an inline or file module and walk its items.
+    fn submodule(
+        &mut self,
+        m: &syn::ItemMod,
+        file: &Path,
+        path: &[String],
+        dir: &Path,
+        public: bool,
+    ) {
+        if is_skipped(&m.attrs) {
+            return;
+        }
+        let name = m.ident.to_string();
+        let public = public && is_pub(&m.vis);
+        let mut child_path = path.to_vec();
+        child_path.push(name.clone());
+        if let Some((_, items)) = &m.content {
+            if public {
+                self.record("mod", &m.ident, doc_text(&m.attrs), file, path);
+            }
+            self.module(file, items, &child_path, &dir.join(&name), public);
+            return;
+        }
+
+        // `#[path]` is relative to the dir of the declaring file.
+        let path_attr = m
+            .attrs
+            .iter()
+            .find(|a| a.path.is_ident("path"))
+            .and_then(|a| match a.parse_meta() {
+                Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                    lit: syn::Lit::Str(s),
+                    ..
+                })) => Some(s.value()),
+                _ => None,
+            });
+        let child = match path_attr {
+            Some(p) => file.parent().unwrap_or(dir).join(p),
+            None => {
+                let flat = dir.join(format!("{}.rs", name));
+                if flat.is_file() {
+                    flat
+                } else {
+                    dir.join(&name).join("mod.rs")
+                }
+            }
+        };
+        if !self.visited.insert(child.clone()) {
+            return;
+        }
+        let parsed = match parse_file(&child) {
+            Ok(f) => f,
+            Err(e) => {
+                log::debug!("DocCoverage: skip module {}: {:?}", name, e);
+                return;
+            }
+        };
+        if public {
+            // Docs of a file module are on its declaration or `//!` in the file.
+            let doc = match (doc_text(&m.attrs), doc_text(&parsed.attrs)) {
+                (None, None) => None,
+                (a, b) => Some(a.unwrap_or_default() + &b.unwrap_or_default()),
+            };
+            self.record("mod", &m.ident, doc, file, path);
+        }
+        let child_dir = if child.file_name().map_or(false, |n| n == "mod.rs") {
+            child.parent().map(Path::to_path_buf).unwrap_or_default()
+        } else {
+            child.with_extension("")
+        };
+        self.module(&child, &parsed.items, &child_path, &child_dir, public);
+    }
+}
+
+/// Doc coverage check struct
+#[derive(Debug)]
+pub struct DocCoverage {
+    pub project_cfg: config::ProjectInfoConfig,
+    pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
+    /// Root file of the library target, `None` for a member without a library.
+    pub root: Option<PathBuf>,
+    pub items: Vec<DocItem>,
+    pub crate_doc: bool,
+    pub missing_docs_lint: Option<String>,
+    /// Builds the docs with `cargo doc`.
+    pub rustdoc: Rustdoc,
+    pub count_result: Option<result::CountResultDetail>,
+}
+
+impl DocCoverage {
+    fn doc_check_cfg(&self) -> config::DocEvaluationConfig {
+        self.config
+            .quality_evaluation_cfg
+            .as_ref()
+            .and_then(|q| q.doc_check_cfg.clone())
+            .unwrap_or_default()
+    }
+
+    fn documented(&self) -> u64 {
+        self.items.iter().filter(|i| i.documented).count() as u64
+    }
+
+    fn functions_with_examples(&self) -> u64 {
+        self.items
+            .iter()
+            .filter(|i| i.examples == Some(true))
+            .count() as u64
+    }
+
+    fn write_result_file(&self) -> Result<()> {
+        let path = &self.project_cfg.result;
+        let report = Report {
+            items: &self.items,
+            findings: &self.rustdoc.findings,
+        };
+        std::fs::write(path, toml::to_string(&report)?)
+            .with_context(|| format!("Write doc coverage result failed! {:?}", path))
+    }
+}
+
+impl CheckToolOption for DocCoverage {
+    /// Find the library target of the member, walk its module tree and build its docs.
+    fn check(&mut self) -> Result<()> {
+        log::info!("DocCoverage check: {:?}", self.project_cfg);
+        let package = self.project_cfg.package.as_deref();
+        let lib = self.context.targets.iter().find(|t| {
+            package.map_or(true, |p| t.package == p)
+                && t.target
+                    .kind
+                    .iter()
+                    .any(|k| k.ends_with("lib") || k == "proc-macro")
+        });
+        let lib = match lib {
+            Some(t) => t,
+            None => {
+                log::info!("DocCoverage: {:?} has no library target", package);
+                return Ok(());
+            }
+        };
+        let root = lib.src_path();
+        let parsed = parse_file(&root)?;
+        self.crate_doc = doc_text(&parsed.attrs).is_some();
+        self.missing_docs_lint = missing_docs_lint(&parsed.attrs);
+        let mut walker = Walker {
+            package_dir: &self.project_cfg.dir,
+            krate: lib.target.name.replace('-', "_"),
+            items: vec![],
+            visited: HashSet::from([root.clone()]),
+        };
+        let dir = root.parent().map(Path::to_path_buf).unwrap_or_default();
+        walker.module(&root, &parsed.items, &[], &dir, true);
+        self.items = walker.items;
+        self.root = Some(root);
+        self.rustdoc.check()?;
+        self.rustdoc.parse()
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        if self.root.is_none() {
+            return Ok(());
+        }
+        log::info!(
+            "DocCoverage parse: {} of {} public items documented",
+            self.documented(),
+            self.items.len()
+        );
+        self.write_result_file()
+    }
+
+    /// Each undocumented item, function without examples and rustdoc diagnostic costs its
+    /// score, so do missing crate docs, a `missing_docs` lint which is not enforced and docs
+    /// which fail to build.
+    fn count(&mut self) -> Result<()> {
+        if self.root.is_none() {
+            return Ok(());
+        }
+        let cfg = self.doc_check_cfg();
+        if let config::DocEvaluationConfig {
+            undocumented_item_score: Some(item_score),
+            doc_score: Some(doc_score),
+            doc_weight: Some(doc_weight),
+            ..
+        } = cfg
+        {
+            let undocumented = self.items.len() as u64 - self.documented();
+            let functions = self.items.iter().filter(|i| i.kind == "fn").count() as u64;
+            let mut penalty = undocumented * item_score
+                + (functions - self.functions_with_examples())
+                    * cfg.missing_example_score.unwrap_or(0);
+            if !self.crate_doc {
+                penalty += cfg.missing_crate_doc_score.unwrap_or(0);
+            }
+            let enforced = self
+                .missing_docs_lint
+                .as_deref()
+                .map_or(false, |l| ENFORCING_LINT_LEVELS.contains(&l));
+            if !enforced {
+                penalty += cfg.missing_docs_lint_score.unwrap_or(0);
+            }
+            penalty += self.rustdoc.findings.len() as u64 * cfg.doc_warning_score.unwrap_or(0);
+            if self.rustdoc.success == Some(false) {
+                penalty += cfg.doc_build_failure_score.unwrap_or(doc_score);
+            }
+            let score = doc_score.saturating_sub(penalty) * 100 / doc_score;
+            self.count_result = Some(result::CountResultDetail::Doc {
+                score: Some(score),
+                normalized_score: Some(score * doc_weight / 100),
+            });
+        }
+        Ok(())
+    }
+
+    fn result(&mut self, result: &mut result::Result) -> Result<()> {
+        log::info!("DocCoverage result: {:?}", self.count_result);
+        if self.root.is_none() {
+            return Ok(());
+        }
+        result.artifacts.push(result::Artifact {
+            path: self.project_cfg.result.clone(),
+            check_item: Some(config::QualityEvaluation::Doc.name().to_string()),
+            member: self.project_cfg.package.clone(),
+            format: result::ArtifactFormat::Toml,
+            purpose: "Public items of the library with their docs, and the rustdoc diagnostics"
+                .to_string(),
+        });
+        self.rustdoc.result(result)?;
+        let (score, normalized_score) = match self.count_result {
+            Some(result::CountResultDetail::Doc {
+                score,
+                normalized_score,
+            }) => (score, normalized_score),
+            _ => (None, None),
+        };
+        let items = self.items.len() as u64;
+        let documented = self.documented();
+        result.doc_check = Some(result::DocCheck {
+            score,
+            normalized_score,
+            items,
+            documented,
+            coverage: match items {
+                0 => 100.0,
+                _ => documented as f64 * 100.0 / items as f64,
+            },
+            kinds: KINDS
+                .iter()
+                .map(|k| result::DocKindCoverage {
+                    kind: k.to_string(),
+                    items: self.items.iter().filter(|i| i.kind == *k).count() as u64,
+                    documented: self
+                        .items
+                        .iter()
+                        .filter(|i| i.kind == *k && i.documented)
+                        .count() as u64,
+                })
+                .filter(|k| k.items > 0)
+                .collect(),
+            crate_doc: self.crate_doc,
+            functions_with_examples: self.functions_with_examples(),
+            missing_docs_lint: self.missing_docs_lint.clone(),
+            doc_build: self.rustdoc.success,
+            doc_warnings: result::DocWarnings {
+                broken_intra_doc_links: self.rustdoc.count_of("broken_intra_doc_links"),
+                invalid_codeblock_attributes: self.rustdoc.count_of("invalid_codeblock_attributes"),
+                bare_urls: self.rustdoc.count_of("bare_urls"),
+                private_intra_doc_links: self.rustdoc.count_of("private_intra_doc_links"),
+                other: self
+                    .rustdoc
+                    .findings
+                    .iter()
+                    .filter(|f| !DOC_WARNING_LINTS.contains(&f.lint.as_str()))
+                    .count() as u64,
+            },
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+// Churn × complexity hotspots: files changed often whose functions are complex.
+//
+// The history is read from the local repository with git2. The complexity comes from the
+// functions of the measure check, or of a measure run here when that check didn't run before.
+
+use super::rust_code_analysis::{FuncInfo, FuncKind, RustCodeAnalysis};
+use super::CheckToolOption;
+use crate::config;
+use crate::context;
+use crate::result;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Changes of a source file in the history window.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileChurn {
+    pub commits: u64,
+    /// Author emails.
+    pub authors: BTreeSet<String>,
+    pub lines_added: u64,
+    pub lines_deleted: u64,
+}
+
+/// Git churn check struct
+#[derive(Debug)]
+pub struct GitChurn {
+    pub project_cfg: config::ProjectInfoConfig,
+    pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
+    /// Churn of each Rust file, relative to the package dir.
+    pub files: BTreeMap<PathBuf, FileChurn>,
+    /// Commits read in the window.
+    pub commits: u64,
+    pub hotspots: Vec<result::ChurnHotspot>,
+}
+
+impl GitChurn {
+    fn churn_cfg(&self) -> config::ChurnEvaluationConfig {
+        self.config
+            .quality_evaluation_cfg
+            .as_ref()
+            .and_then(|q| q.churn_cfg.clone())
+            .unwrap_or_default()
+    }
+
+    /// Read the commits of the window, skipping merges, and add up the changes of the
+    /// Rust files of the package.
+    fn read_history(&mut self) -> Result<()> {
+        let cfg = self.churn_cfg();
+        let dir = &self.project_cfg.dir;
+        let repo = git2::Repository::discover(dir)
+            .with_context(|| format!("No git repository found at {:?}", dir))?;
+        let workdir = repo
+            .workdir()
+            .context("The git repository is bare")?
+            .canonicalize()?;
+        let package_dir = dir.canonicalize()?;
+        let rel_dir = package_dir
+            .strip_prefix(&workdir)
+            .with_context(|| format!("{:?} is outside the repository {:?}", dir, workdir))?
+            .to_path_buf();
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let since = now - cfg.since_days.unwrap_or(180) as i64 * 24 * 3600;
+        let mut walk = repo.revwalk()?;
+        walk.push_head()?;
+        walk.set_sorting(git2::Sort::TIME)?;
+
+        for oid in walk {
+            if crate::util::is_cancelled() {
+                return Err(crate::util::InterruptError::Cancelled.into());
+            }
+            let commit = repo.find_commit(oid?)?;
+            if commit.time().seconds() < since
+                || cfg.max_commits.map_or(false, |m| self.commits >= m as u64)
+            {
+                break;
+            }
+            if commit.parent_count() > 1 {
+                continue;
+            }
+            self.commits += 1;
+            let parent_tree = match commit.parent_count() {
+                0 => None,
+                _ => Some(commit.parent(0)?.tree()?),
+            };
+            let mut opts = git2::DiffOptions::new();
+            opts.context_lines(0);
+            if !rel_dir.as_os_str().is_empty() {
+                opts.pathspec(rel_dir.as_path());
+            }
+            let diff = repo.diff_tree_to_tree(
+                parent_tree.as_ref(),
+                Some(&commit.tree()?),
+                Some(&mut opts),
+            )?;
+            let author = commit.author().email().unwrap_or_default().to_string();
+            for (i, delta) in diff.deltas().enumerate() {
+                let path = match delta.new_file().path().or_else(|| delta.old_file().path()) {
+                    Some(p) if p.extension().map_or(false, |x| x == "rs") => p,
+                    _ => continue,
+                };
+                let path = path.strip_prefix(&rel_dir).unwrap_or(path).to_path_buf();
+                let (_, added, deleted) = match git2::Patch::from_diff(&diff, i)? {
+                    Some(p) => p.line_stats()?,
+                    None => (0, 0, 0),
+                };
+                let f = self.files.entry(path).or_default();
+                f.commits += 1;
+                f.authors.insert(author.clone());
+                f.lines_added += added as u64;
+                f.lines_deleted += deleted as u64;
+            }
+        }
+        Ok(())
+    }
+
+    /// Measure the functions of the package, for a churn check without a measure check
+    /// before it. The measure files are written next to the churn result.
+    fn measure(&self, result: &mut result::Result) -> Result<Vec<FuncInfo>> {
+        let mut measure = RustCodeAnalysis::new(
+            config::ProjectInfoConfig {
+                result: self.project_cfg.result.with_file_name("measure_check.txt"),
+                ..self.project_cfg.clone()
+            },
+            self.config.clone(),
+            self.context.clone(),
+        );
+        measure.check()?;
+        measure.parse()?;
+        result
+            .artifacts
+            .extend(measure.artifacts(config::QualityEvaluation::Churn));
+        Ok(measure.func_info)
+    }
+
+    /// Rank the files by `commits × complexity`.
+    fn rank(&mut self, func_info: &[FuncInfo]) {
+        let cfg = self.churn_cfg();
+        let high_churn = cfg.high_churn.unwrap_or(10);
+        let high_complexity = cfg.high_complexity.unwrap_or(50);
+        let root = &self.project_cfg.dir;
+
+        // Functions of each file, the most complex first.
+        let mut functions: BTreeMap<PathBuf, Vec<&FuncInfo>> = BTreeMap::new();
+        for v in func_info
+            .iter()
+            .filter(|v| v.kind == FuncKind::Function && !v.excluded)
+        {
+            let file = Path::new(&v.file);
+            let file = file.strip_prefix(root).unwrap_or(file).to_path_buf();
+            functions.entry(file).or_default().push(v);
+        }
+        let mut hotspots = vec![];
+        for (file, churn) in self.files.iter() {
+            let mut funcs = match functions.remove(file) {
+                Some(f) => f,
+                None => continue,
+            };
+            funcs.sort_by(|a, b| b.cognitive.cmp(&a.cognitive));
+            let complexity: u64 = funcs.iter().map(|v| v.cognitive as u64).sum();
+            hotspots.push(result::ChurnHotspot {
+                file: file.clone(),
+                commits: churn.commits,
+                authors: churn.authors.len() as u64,
+                lines_added: churn.lines_added,
+                lines_deleted: churn.lines_deleted,
+                complexity,
+                score: churn.commits * complexity,
+                flagged: churn.commits >= high_churn && complexity >= high_complexity,
+                functions: funcs
+                    .iter()
+                    .take(3)
+                    .map(|v| v.qualified_name.clone())
+                    .collect(),
+            });
+        }
+        hotspots.sort_by(|a, b| b.score.cmp(&a.score));
+        self.hotspots = hotspots;
+    }
+
+    fn write_result_file(&self) -> Result<()> {
+        let path = &self.project_cfg.result;
+        std::fs::write(path, toml::to_string(&self.files)?)
+            .with_context(|| format!("Write churn result failed! {:?}", path))
+    }
+}
+
+impl CheckToolOption for GitChurn {
+    fn check(&mut self) -> Result<()> {
+        log::info!("GitChurn check: {:?}", self.project_cfg);
+        self.read_history()
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        self.write_result_file()
+    }
+
+    /// The churn check is not scored.
+    fn count(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Rank the files with the functions of the measure check.
+    fn result(&mut self, result: &mut result::Result) -> Result<()> {
+        let func_info = match result.measured.clone() {
+            Some(f) => f,
+            None => Arc::new(self.measure(result)?),
+        };
+        if func_info.is_empty() {
+            log::warn!("GitChurn: no function measured, is `measure_cfg` configured?");
+        }
+        self.rank(&func_info);
+        log::info!("GitChurn result: {} hotspots", self.hotspots.len());
+        let cfg = self.churn_cfg();
+        let top = cfg.top_hotspots.unwrap_or(10);
+        result.artifacts.push(result::Artifact {
+            path: self.project_cfg.result.clone(),
+            check_item: Some(config::QualityEvaluation::Churn.name().to_string()),
+            member: self.project_cfg.package.clone(),
+            format: result::ArtifactFormat::Toml,
+            purpose: "Commits, authors and changed lines of each Rust file in the window"
+                .to_string(),
+        });
+        result.churn = Some(result::ChurnCheck {
+            since_days: cfg.since_days.unwrap_or(180),
+            commits: self.commits,
+            files: self.files.len() as u64,
+            flagged: self.hotspots.iter().filter(|h| h.flagged).count() as u64,
+            // The top files, and every flagged one.
+            hotspots: self
+                .hotspots
+                .iter()
+                .enumerate()
+                .filter(|(i, h)| *i < top || h.flagged)
+                .map(|(_, h)| h.clone())
+                .collect(),
+        });
+        Ok(())
+    }
+}
@@ -1,17 +1,26 @@
 pub mod cargo_deny;
 pub mod clippy;
+pub mod doc_coverage;
+pub mod git_churn;
 pub mod rust_code_analysis;
+pub mod rustdoc;
+pub mod token_clone;
 
 use crate::config;
+use crate::context;
 use crate::result;
 use anyhow::Result;
 use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 pub enum CheckTool {
     ClippyForStaticCheck,
     CargoDenyForLicenseCheck,
     RustCodeAnalysisForMeasure,
+    GitForChurn,
+    TokensForDuplication,
+    SynForDocCoverage,
 }
 
 /// Trait for integrating tools.
@@ -31,17 +40,23 @@ pub fn make_check(
     cfg: config::Config,
     tool: CheckTool,
     project_cfg: &config::ProjectInfoConfig,
+    ctx: Arc<context::ProjectContext>,
 ) -> Box<dyn CheckToolOption> {
     let project = config::ProjectInfoConfig {
         dir: project_cfg.dir.clone(),
         manifest: project_cfg.manifest.clone(),
+        package: project_cfg.package.clone(),
         result: make_result_path(&tool, &project_cfg.result),
     };
     match tool {
         CheckTool::ClippyForStaticCheck => Box::new(clippy::Clippy {
             project_cfg: project,
             config: cfg,
+            context: ctx,
             lint_info: HashMap::new(),
+            suppressions: vec![],
+            touched: 0,
+            untouched: 0,
             check_result: None,
             parse_result: None,
             count_result: None,
@@ -49,6 +64,7 @@ pub fn make_check(
         CheckTool::CargoDenyForLicenseCheck => Box::new(cargo_deny::LicenseCheck {
             project_cfg: project,
             config: cfg,
+            context: ctx,
             crate_licenses: BTreeMap::new(),
             license_crates: vec![],
             unlicense_crates: vec![],
@@ -57,13 +73,42 @@ pub fn make_check(
             parse_result: None,
             count_result: None,
         }),
-        CheckTool::RustCodeAnalysisForMeasure => Box::new(rust_code_analysis::RustCodeAnalysis {
+        CheckTool::RustCodeAnalysisForMeasure => {
+            Box::new(rust_code_analysis::RustCodeAnalysis::new(project, cfg, ctx))
+        }
+        CheckTool::GitForChurn => Box::new(git_churn::GitChurn {
             project_cfg: project,
             config: cfg,
-            dir_list: HashMap::new(),
-            func_info: vec![],
-            check_result: None,
-            parse_result: None,
+            context: ctx,
+            files: BTreeMap::new(),
+            commits: 0,
+            hotspots: vec![],
+        }),
+        CheckTool::TokensForDuplication => Box::new(token_clone::TokenClone {
+            project_cfg: project,
+            config: cfg,
+            context: ctx,
+            sources: vec![],
+            blocks: vec![],
+            files: vec![],
+            count_result: None,
+        }),
+        CheckTool::SynForDocCoverage => Box::new(doc_coverage::DocCoverage {
+            rustdoc: rustdoc::Rustdoc::new(
+                config::ProjectInfoConfig {
+                    result: project.result.with_file_name("doc_build.json"),
+                    ..project.clone()
+                },
+                cfg.clone(),
+                ctx.clone(),
+            ),
+            project_cfg: project,
+            config: cfg,
+            context: ctx,
+            root: None,
+            items: vec![],
+            crate_doc: false,
+            missing_docs_lint: None,
             count_result: None,
         }),
     }
@@ -78,6 +123,9 @@ fn make_result_path(c: &CheckTool, dir: &PathBuf) -> PathBuf {
         CheckTool::ClippyForStaticCheck => result_path.push(r"static_check.txt"),
         CheckTool::CargoDenyForLicenseCheck => result_path.push(r"license_check.txt"),
         CheckTool::RustCodeAnalysisForMeasure => result_path.push(r"measure_check.txt"),
+        CheckTool::GitForChurn => result_path.push(r"churn_check.toml"),
+        CheckTool::TokensForDuplication => result_path.push(r"duplication_check.toml"),
+        CheckTool::SynForDocCoverage => result_path.push(r"doc_check.toml"),
     }
     result_path
 }
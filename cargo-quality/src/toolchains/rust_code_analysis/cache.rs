@@ -0,0 +1,132 @@
+// On-disk cache of the function spaces of each source file, so only the files changed
+// since the previous run are analyzed again.
+//
+// An entry is keyed by a hash of the file path and content, the rust-code-analysis revision
+// and the analysis options. The thresholds are applied after the cache, so changing them
+// does not invalidate it. Entries are written to a temporary file and renamed, so runs
+// sharing the cache dir never read a partial entry.
+
+use super::{FuncInfo, FuncKind};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::hash::Hasher;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use twox_hash::XxHash64;
+
+/// Revision of rust-code-analysis in `Cargo.toml`, read by `build.rs`; its metrics may change
+/// between revisions.
+const RCA_REVISION: &str = env!("RCA_REVISION");
+
+/// Version of the entry format, bumped when `FuncInfo` or the analysis options change.
+const ENTRY_VERSION: &str = "1";
+
+/// A function or file space with the context skipped by the serialization of `FuncInfo`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedSpace {
+    pub info: FuncInfo,
+    pub scope: Vec<(FuncKind, String)>,
+    pub closure: Option<usize>,
+}
+
+impl CachedSpace {
+    pub fn new(v: &FuncInfo) -> Self {
+        CachedSpace {
+            info: v.clone(),
+            scope: v.scope.clone(),
+            closure: v.closure,
+        }
+    }
+
+    pub fn into_info(self) -> FuncInfo {
+        FuncInfo {
+            scope: self.scope,
+            closure: self.closure,
+            ..self.info
+        }
+    }
+}
+
+/// The analysis of a source file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub spaces: Vec<CachedSpace>,
+    /// The rust-code-analysis output written to the `details` dir.
+    pub details: String,
+}
+
+#[derive(Debug)]
+pub struct MeasureCache {
+    dir: PathBuf,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl MeasureCache {
+    pub fn new(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Create measure cache dir failed! {:?}", dir))?;
+        Ok(MeasureCache {
+            dir: dir.to_path_buf(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// Key of the analysis of the file with the content.
+    pub fn key(path: &Path, source: &[u8]) -> String {
+        let hash = |seed| {
+            let mut h = XxHash64::with_seed(seed);
+            let path = path.to_string_lossy();
+            for part in [RCA_REVISION, ENTRY_VERSION, path.as_ref()] {
+                h.write(part.as_bytes());
+                h.write_u8(0);
+            }
+            h.write(source);
+            h.finish()
+        };
+        format!("{:016x}{:016x}", hash(0), hash(1))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{}.cbor", key))
+    }
+
+    /// The entry of the key, `None` if missing or unreadable.
+    pub fn get(&self, key: &str) -> Option<Entry> {
+        let entry = std::fs::read(self.entry_path(key))
+            .ok()
+            .and_then(|data| serde_cbor::from_slice(&data).ok());
+        match entry {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        entry
+    }
+
+    /// Write the entry atomically. A failure only costs a later analysis, so it is logged.
+    pub fn put(&self, key: &str, entry: &Entry) {
+        let path = self.entry_path(key);
+        let write = || -> Result<()> {
+            let dir = path.parent().context("no parent dir")?;
+            std::fs::create_dir_all(dir)?;
+            let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+            // CBOR keeps the NaN of the metrics of an empty function, unlike JSON.
+            tmp.write_all(&serde_cbor::to_vec(entry)?)?;
+            tmp.persist(&path)?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            log::debug!("Write measure cache entry {:?} failed: {:?}", path, e);
+        }
+    }
+
+    /// Number of the files found in the cache and analyzed.
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
@@ -1,5 +1,11 @@
+mod cache;
+pub mod qualified;
+mod stats;
+
 use crate::config;
+use crate::context;
 use crate::result;
+use crate::suppress;
 use crate::util;
 
 use anyhow::{Context, Result};
@@ -8,19 +14,15 @@ use globset::{Glob, GlobSet, GlobSetBuilder};
 use log;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::{hash_map, HashMap};
-use std::fs::{File, OpenOptions};
+use std::collections::{hash_map, BTreeMap, HashMap};
+use std::fs::File;
 use std::io::Write;
 use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
-use std::process;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::thread::available_parallelism;
-use walkdir::DirEntry;
 
-extern crate lazy_static;
-use lazy_static::lazy_static;
 use rust_code_analysis::ParserTrait;
 use rust_code_analysis::{
     action, get_from_ext, get_function_spaces, get_ops, guess_language, preprocess, read_file,
@@ -33,10 +35,6 @@ use rust_code_analysis::{
 };
 use rust_code_analysis::{FuncSpace, LANG};
 
-lazy_static! {
-    static ref FUNC_SPACE_RESULT: Mutex<Vec<FuncInfo>> = Mutex::new(vec![]);
-}
-
 // fixme: copy and edit from rust-code-analysis
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -63,17 +61,17 @@ pub enum FuncKind {
 }
 
 // fixme: copy and edit from rust-code-analysis
-fn get_space_kind_name(s: &SpaceKind) -> &str {
+fn get_space_kind(s: &SpaceKind) -> FuncKind {
     match s {
-        SpaceKind::Unknown => "unknown",
-        SpaceKind::Function => "function",
-        SpaceKind::Class => "class",
-        SpaceKind::Struct => "struct",
-        SpaceKind::Trait => "trait",
-        SpaceKind::Impl => "impl",
-        SpaceKind::Unit => "unit",
-        SpaceKind::Namespace => "namespace",
-        SpaceKind::Interface => "interface",
+        SpaceKind::Unknown => FuncKind::Unknown,
+        SpaceKind::Function => FuncKind::Function,
+        SpaceKind::Class => FuncKind::Class,
+        SpaceKind::Struct => FuncKind::Struct,
+        SpaceKind::Trait => FuncKind::Trait,
+        SpaceKind::Impl => FuncKind::Impl,
+        SpaceKind::Unit => FuncKind::Unit,
+        SpaceKind::Namespace => FuncKind::Namespace,
+        SpaceKind::Interface => FuncKind::Interface,
     }
 }
 
@@ -81,7 +79,10 @@ fn get_space_kind_name(s: &SpaceKind) -> &str {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct FuncInfo {
     pub name: String,
-    pub path: String,
+    /// Source file of the function or file space.
+    pub file: String,
+    /// e.g. `my_crate::parser::Lexer::next_token`, see `qualified`.
+    pub qualified_name: String,
     pub start_line: usize,
     pub end_line: usize,
     pub if_large_file: bool,
@@ -91,60 +92,266 @@ pub struct FuncInfo {
     pub if_large_cognitive: bool,
     pub cyclomatic: usize,
     pub if_large_cyclomatic: bool,
+    pub halstead_volume: f64,
+    pub halstead_difficulty: f64,
+    pub halstead_effort: f64,
+    /// Maintainability index, Visual Studio variant from 0 to 100.
+    pub maintainability_index: f64,
+    /// ABC magnitude.
+    pub abc: f64,
+    /// Number of functions and closures.
+    pub nom: usize,
+    pub nargs: usize,
+    pub nexits: usize,
+    pub sloc: usize,
+    pub ploc: usize,
+    pub lloc: usize,
+    pub cloc: usize,
+    pub blank: usize,
+    /// Metrics past their threshold, e.g. `nargs`.
+    pub exceeded: Vec<String>,
+    /// Path pattern of the `measure_cfg.overrides` block whose thresholds applied.
+    pub threshold_override: Option<String>,
+    /// Exceeded thresholds accepted by a suppression comment.
+    pub suppressed: Vec<String>,
+    /// Production, test, bench, example or build script code.
+    pub class: config::CodeClass,
+    /// Left out of the score with its class.
+    pub excluded: bool,
+    /// Sum of the excess `value / threshold - 1` over the metrics past their threshold and not
+    /// suppressed, ranks the hotspots.
+    pub hotspot_score: f64,
+    /// Whether the space is in the changes of `check --since`, unset without `--since`.
+    pub changed: Option<bool>,
+    /// Enclosing impls, traits and functions in the file, outermost first.
+    #[serde(skip)]
+    pub scope: Vec<(FuncKind, String)>,
+    /// Index of a closure among the closures of its enclosing space.
+    #[serde(skip)]
+    pub closure: Option<usize>,
+    /// Kept last, as a table must follow the plain values in toml.
+    pub qualified: qualified::QualifiedName,
 }
 
-/// Save the results of rust-code-analysis in thread-local variables.
-fn save_funcspace(path: String, f: &FuncSpace) {
-    if f.kind == SpaceKind::Unit {
-        if let Some(n) = &f.name {
-            let s = FuncInfo {
-                path: n.clone(),
-                name: n.clone(),
-                start_line: f.start_line,
-                end_line: f.end_line,
-                kind: FuncKind::Unit,
-                cognitive: 0,
-                cyclomatic: 0,
-                ..Default::default()
+impl FuncInfo {
+    /// Size of the space in the lines of `measure`.
+    fn size(&self, measure: config::SizeMeasure) -> usize {
+        match measure {
+            config::SizeMeasure::Lines => self.end_line - self.start_line,
+            config::SizeMeasure::Sloc => self.sloc,
+            config::SizeMeasure::Ploc => self.ploc,
+            config::SizeMeasure::Lloc => self.lloc,
+        }
+    }
+
+    /// Value of a measure metric, `None` if it is not checked for this kind of space.
+    fn metric(&self, metric: &str, size: config::SizeMeasure) -> Option<f64> {
+        let rows = self.size(size) as f64;
+        if self.kind != FuncKind::Function {
+            return match metric {
+                suppress::NUM_ROWS_FILE => Some(rows),
+                suppress::NOM => Some(self.nom as f64),
+                _ => None,
+            };
+        }
+        Some(match metric {
+            suppress::NUM_ROWS_FUNCTION => rows,
+            suppress::COGNITIVE_COMPLEXITY => self.cognitive as f64,
+            suppress::CYCLOMATIC_COMPLEXITY => self.cyclomatic as f64,
+            suppress::HALSTEAD_VOLUME => self.halstead_volume,
+            suppress::MAINTAINABILITY_INDEX => self.maintainability_index,
+            suppress::ABC => self.abc,
+            suppress::NARGS => self.nargs as f64,
+            suppress::NEXITS => self.nexits as f64,
+            _ => return None,
+        })
+    }
+
+    /// Record that the metric is past its threshold.
+    fn mark(&mut self, metric: &str) {
+        match metric {
+            suppress::NUM_ROWS_FILE => self.if_large_file = true,
+            suppress::NUM_ROWS_FUNCTION => self.if_large_function = true,
+            suppress::COGNITIVE_COMPLEXITY => self.if_large_cognitive = true,
+            suppress::CYCLOMATIC_COMPLEXITY => self.if_large_cyclomatic = true,
+            _ => {}
+        }
+        self.exceeded.push(metric.to_string());
+    }
+}
+
+/// Whether the metric has a `small_*` threshold, i.e. lower values are worse.
+fn lower_is_worse(metric: &str) -> bool {
+    metric == suppress::MAINTAINABILITY_INDEX
+}
+
+/// A function or file past a threshold of the measure check.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub metric: &'static str,
+    /// How many times past the threshold the value is, at least 1.
+    pub ratio: f64,
+    pub class: config::CodeClass,
+}
+
+/// Thresholds of the measure check which apply to a source file.
+#[derive(Debug, Clone)]
+struct Thresholds {
+    values: HashMap<&'static str, f64>,
+    /// Path pattern of the override block, if one matched.
+    matched: Option<String>,
+}
+
+/// Resolve the thresholds of each source file and class of code from `measure_cfg`, its
+/// `classes` and its `overrides`.
+struct ThresholdResolver<'a> {
+    base: &'a config::MeasureEvaluationConfig,
+    root: PathBuf,
+    /// Overrides ordered from the most specific, with their compiled pattern.
+    overrides: Vec<(globset::GlobMatcher, &'a config::MeasureOverride)>,
+    cache: HashMap<(String, config::CodeClass), Thresholds>,
+}
+
+impl<'a> ThresholdResolver<'a> {
+    fn new(base: &'a config::MeasureEvaluationConfig, root: &Path) -> Result<Self> {
+        let mut overrides = vec![];
+        for o in base.overrides.iter().flatten() {
+            let glob = Glob::new(&o.path)
+                .with_context(|| format!("Invalid override path: {:?}", o.path))?;
+            overrides.push((glob.compile_matcher(), o));
+        }
+        // Stable sort, so a later block wins a tie once reversed.
+        overrides.sort_by_key(|(_, o)| o.specificity());
+        overrides.reverse();
+        Ok(ThresholdResolver {
+            base,
+            root: root.to_path_buf(),
+            overrides,
+            cache: HashMap::new(),
+        })
+    }
+
+    fn get(&mut self, file: &str, class: config::CodeClass) -> &Thresholds {
+        let key = (file.to_string(), class);
+        if !self.cache.contains_key(&key) {
+            let path = Path::new(file);
+            let path = path.strip_prefix(&self.root).unwrap_or(path);
+            let o = self
+                .overrides
+                .iter()
+                .find(|(g, _)| g.is_match(path))
+                .map(|(_, o)| *o);
+            let c = self.base.classes.as_ref().and_then(|c| c.get(class));
+            let values = suppress::MEASURE_METRICS
+                .iter()
+                .filter_map(|m| {
+                    o.and_then(|o| o.threshold(m))
+                        .or_else(|| c.and_then(|c| c.threshold(m)))
+                        .or_else(|| self.base.threshold(m))
+                        .map(|t| (*m, t as f64))
+                })
+                .collect();
+            let t = Thresholds {
+                values,
+                matched: o.map(|o| o.path.clone()),
             };
-            FUNC_SPACE_RESULT.lock().unwrap().push(s);
+            self.cache.insert(key.clone(), t);
         }
-    } else if f.kind == SpaceKind::Function {
-        if let Some(n) = &f.name {
+        &self.cache[&key]
+    }
+}
+
+/// Fill the `loc` metrics of the space.
+fn with_loc(mut s: FuncInfo, f: &FuncSpace) -> FuncInfo {
+    let loc = &f.metrics.loc;
+    s.sloc = loc.sloc() as usize;
+    s.ploc = loc.ploc() as usize;
+    s.lloc = loc.lloc() as usize;
+    s.cloc = loc.cloc() as usize;
+    s.blank = loc.blank() as usize;
+    s
+}
+
+/// Save the results of rust-code-analysis to `out`.
+/// `scope` holds the impls, traits and functions enclosing `f` in `file`.
+fn save_funcspace(
+    file: &str,
+    scope: &mut Vec<(FuncKind, String)>,
+    closure: Option<usize>,
+    f: &FuncSpace,
+    out: &mut Vec<FuncInfo>,
+) {
+    let name = match closure {
+        Some(i) => qualified::closure_name(i),
+        None => f
+            .name
+            .clone()
+            .unwrap_or_else(|| "parse_func_name_failed".to_string()),
+    };
+    let file = if f.kind == SpaceKind::Unit {
+        let file = f.name.clone().unwrap_or_default();
+        let s = FuncInfo {
+            file: file.clone(),
+            name: name.clone(),
+            start_line: f.start_line,
+            end_line: f.end_line,
+            kind: FuncKind::Unit,
+            cognitive: 0,
+            cyclomatic: 0,
+            nom: f.metrics.nom.total() as usize,
+            ..Default::default()
+        };
+        out.push(with_loc(s, f));
+        file
+    } else {
+        if f.kind == SpaceKind::Function {
             let s = FuncInfo {
-                path: path.clone().as_mut_str().to_owned()
-                    + "&&&FuncName:"
-                    + n.as_str()
-                    + "&&&Kind:"
-                    + get_space_kind_name(&f.kind),
-                name: n.clone(),
+                file: file.to_string(),
+                name: name.clone(),
                 start_line: f.start_line,
                 end_line: f.end_line,
                 kind: FuncKind::Function,
                 cognitive: f.metrics.cognitive.cognitive() as usize,
                 cyclomatic: f.metrics.cyclomatic.cyclomatic() as usize,
+                halstead_volume: f.metrics.halstead.volume(),
+                halstead_difficulty: f.metrics.halstead.difficulty(),
+                halstead_effort: f.metrics.halstead.effort(),
+                maintainability_index: f.metrics.mi.mi_visual_studio(),
+                abc: f.metrics.abc.magnitude(),
+                nom: f.metrics.nom.total() as usize,
+                nargs: f.metrics.nargs.fn_args() as usize,
+                nexits: f.metrics.nexits.exit() as usize,
+                scope: scope.clone(),
+                closure,
                 ..Default::default()
             };
-            FUNC_SPACE_RESULT.lock().unwrap().push(s);
+            out.push(with_loc(s, f));
         }
+        file.to_string()
+    };
+    let nested = f.kind != SpaceKind::Unit;
+    if nested {
+        scope.push((get_space_kind(&f.kind), name));
     }
-    if !f.spaces.is_empty() {
-        for i in f.spaces.iter() {
-            let p: String = if path.is_empty() {
-                f.name.as_ref().unwrap().clone()
-            } else {
-                path.clone()
-                    + "&&&Name:"
-                    + f.name
-                        .as_ref()
-                        .unwrap_or(&"parse_func_name_failed".to_string())
-                        .as_str()
-                    + "&&&Kind:"
-                    + get_space_kind_name(&f.kind)
-            };
-            save_funcspace(p, i);
-        }
+    // Closures are numbered in their order in the enclosing space, as rustc does.
+    let mut closures = 0;
+    for i in f.spaces.iter() {
+        let closure = (i.kind == SpaceKind::Function && is_closure(i)).then(|| {
+            closures += 1;
+            closures - 1
+        });
+        save_funcspace(&file, scope, closure, i, out);
     }
+    if nested {
+        scope.pop();
+    }
+}
+
+/// rust-code-analysis names a closure `<anonymous>`, or not at all.
+fn is_closure(f: &FuncSpace) -> bool {
+    f.name.as_deref().map_or(true, |n| {
+        !n.starts_with(|c: char| c.is_alphabetic() || c == '_')
+    })
 }
 
 // fixme: copy and edit from rust-code-analysis
@@ -204,34 +411,7 @@ impl Format {
                 Format::Yaml => ".yml",
             };
 
-            // Remove root /
-            let path = path.strip_prefix("/").unwrap_or(path);
-
-            // Remove root ./
-            let path = path.strip_prefix("./").unwrap_or(path);
-
-            // Replace .. with . to keep files inside the output folder
-            let cleaned_path: Vec<&str> = path
-                .iter()
-                .map(|os_str| {
-                    let s_str = os_str.to_str().unwrap();
-                    if s_str == ".." {
-                        "."
-                    } else {
-                        s_str
-                    }
-                })
-                .collect();
-            let mut path_name = "file_result___".to_string();
-            for str in cleaned_path.iter() {
-                //log::info!("output path: str: {:?}", str);
-                if !str.contains('\\') && !str.contains('/') && !str.contains(':') {
-                    path_name += str;
-                    path_name += "___";
-                }
-            }
-            path_name = path_name[0..path_name.len() - 1].to_string() + format_ext;
-
+            let path_name = output_file_name(path, format_ext);
             let mut format_file =
                 File::create(output_path.as_ref().unwrap().join(path_name)).unwrap();
             match self {
@@ -261,6 +441,38 @@ impl Format {
     }
 }
 
+// fixme: copy and edit from rust-code-analysis
+/// Name of the output file of a source file, e.g. `file_result___src___lib.rs.toml`.
+fn output_file_name(path: &Path, format_ext: &str) -> String {
+    // Remove root /
+    let path = path.strip_prefix("/").unwrap_or(path);
+
+    // Remove root ./
+    let path = path.strip_prefix("./").unwrap_or(path);
+
+    // Replace .. with . to keep files inside the output folder
+    let cleaned_path: Vec<&str> = path
+        .iter()
+        .map(|os_str| {
+            let s_str = os_str.to_str().unwrap();
+            if s_str == ".." {
+                "."
+            } else {
+                s_str
+            }
+        })
+        .collect();
+    let mut path_name = "file_result___".to_string();
+    for str in cleaned_path.iter() {
+        //log::info!("output path: str: {:?}", str);
+        if !str.contains('\\') && !str.contains('/') && !str.contains(':') {
+            path_name += str;
+            path_name += "___";
+        }
+    }
+    path_name[0..path_name.len() - 1].to_string() + format_ext
+}
+
 // fixme: copy and edit from rust-code-analysis
 impl FromStr for Format {
     type Err = String;
@@ -296,6 +508,12 @@ struct Config {
     preproc_lock: Option<Arc<Mutex<PreprocResults>>>,
     preproc: Option<Arc<PreprocResults>>,
     count_lock: Option<Arc<Mutex<Count>>>,
+    /// Function spaces of the files analyzed by a previous run.
+    cache: Option<Arc<cache::MeasureCache>>,
+    /// Function spaces of the files analyzed by this run.
+    spaces: Arc<Mutex<Vec<FuncInfo>>>,
+    /// Cancellation signal of the check item, polled by the workers.
+    cancel: util::CancelToken,
 }
 
 // fixme: copy and edit from rust-code-analysis
@@ -315,11 +533,31 @@ fn mk_globset(elems: Vec<String>) -> GlobSet {
 
 // fixme: copy and edit from rust-code-analysis
 fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<()> {
+    // Drain the remaining files quickly once the evaluation is cancelled.
+    if cfg.cancel.is_cancelled() {
+        return Ok(());
+    }
     let source = if let Some(source) = read_file_with_eol(&path)? {
         source
     } else {
         return Ok(());
     };
+    let key = cfg
+        .cache
+        .as_ref()
+        .map(|_| cache::MeasureCache::key(&path, &source));
+    if let (Some(c), Some(k), true) = (&cfg.cache, &key, cfg.metrics) {
+        if let Some(entry) = c.get(k) {
+            if let Some(output) = &cfg.output {
+                std::fs::write(output.join(output_file_name(&path, ".toml")), entry.details)?;
+            }
+            cfg.spaces
+                .lock()
+                .unwrap()
+                .extend(entry.spaces.into_iter().map(cache::CachedSpace::into_info));
+            return Ok(());
+        }
+    }
 
     let language = if let Some(language) = cfg.language {
         language
@@ -339,7 +577,16 @@ fn act_on_file(path: PathBuf, cfg: &Config) -> std::io::Result<()> {
     } else if cfg.metrics {
         if let Some(output_format) = &cfg.output_format {
             if let Some(space) = get_function_spaces(&language, source, &path, pr) {
-                save_funcspace("".to_string(), &space);
+                let mut spaces = vec![];
+                save_funcspace("", &mut vec![], None, &space, &mut spaces);
+                if let (Some(c), Some(k)) = (&cfg.cache, &key) {
+                    let entry = cache::Entry {
+                        spaces: spaces.iter().map(cache::CachedSpace::new).collect(),
+                        details: toml::to_string(&space).unwrap_or_default(),
+                    };
+                    c.put(k, &entry);
+                }
+                cfg.spaces.lock().unwrap().extend(spaces);
                 output_format.dump_formats(&space, &path, &cfg.output, cfg.pretty)
             } else {
                 Ok(())
@@ -494,8 +741,17 @@ struct Opts {
 pub struct RustCodeAnalysis {
     pub project_cfg: config::ProjectInfoConfig,
     pub config: config::Config,
-    pub dir_list: HashMap<PathBuf, DirEntry>,
+    pub context: Arc<context::ProjectContext>,
+    pub source_files: util::SourceFiles,
+    /// Function spaces collected by the analysis workers.
+    pub spaces: Arc<Mutex<Vec<FuncInfo>>>,
     pub func_info: Vec<FuncInfo>,
+    pub suppressions: Vec<result::Suppression>,
+    pub findings: Vec<Finding>,
+    /// Exceeded thresholds outside the changes of `check --since`, not scored.
+    pub untouched: u64,
+    pub penalties: Vec<result::MetricPenalty>,
+    pub classes: Vec<result::ClassMeasure>,
     pub check_result: Option<result::CheckResultDetail>,
     pub parse_result: Option<result::ParseResultDetail>,
     pub count_result: Option<result::CountResultDetail>,
@@ -505,28 +761,28 @@ impl super::CheckToolOption for RustCodeAnalysis {
     // fixme: copy and edit from rust-code-analysis/rust-code-analysis-cli
     fn check(&mut self) -> Result<()> {
         log::info!("RustCodeAnalysis check: {:?}", self.project_cfg);
-        // Get subdirectories under the project.
-        let exclude_dir = self.config.exclude_dir.clone().unwrap_or_default();
-        self.dir_list = util::get_all_dir(
-            self.project_cfg.dir.clone(),
-            r"src",
-            exclude_dir,
-            util::dir_and_name,
+        // Get the source files of the workspace targets.
+        let exclude = util::make_globset(&self.config.exclude_dir.clone().unwrap_or_default())?;
+        self.source_files = util::get_target_files(
+            &self.context,
+            &self.config.source_cfg.clone().unwrap_or_default(),
+            self.project_cfg.package.as_deref(),
+            &self.project_cfg.dir,
+            &exclude,
         )?;
         log::info!(
-            "RustCodeAnalysis check path: {:?} paths:{:?}",
+            "RustCodeAnalysis check path: {:?} files:{:?} excluded:{:?}",
             self.project_cfg.dir.clone(),
-            self.dir_list
+            self.source_files.files,
+            self.source_files.excluded
         );
         // Used to store the scan results of each source file.
-        let mut output = self.project_cfg.result.clone();
-        output.pop();
-        output.push("details");
+        let output = self.details_path();
         std::fs::create_dir_all(&output)
             .with_context(|| format!("Create result dir path failed! {:?}", output))?;
 
         let opts = Opts {
-            paths: self.dir_list.clone().into_keys().collect(),
+            paths: self.source_files.files.iter().cloned().collect(),
             dump: false,
             comments: false,
             find: vec!["Vec::new()".to_owned()],
@@ -572,8 +828,7 @@ impl super::CheckToolOption for RustCodeAnalysis {
 
         let output_is_dir = opts.output.as_ref().map(|p| p.is_dir()).unwrap_or(false);
         if (opts.metrics || opts.ops) && opts.output.is_some() && !output_is_dir {
-            log::info!("Error: The output parameter must be a directory");
-            process::exit(1);
+            anyhow::bail!("The output path must be a directory! {:?}", opts.output);
         }
 
         let typ = opts.language_type.unwrap_or_default();
@@ -622,6 +877,9 @@ impl super::CheckToolOption for RustCodeAnalysis {
             preproc_lock,
             preproc,
             count_lock,
+            cache: self.measure_cache()?,
+            spaces: self.spaces.clone(),
+            cancel: util::CancelToken::current(),
         };
 
         let files_data = FilesData {
@@ -630,16 +888,22 @@ impl super::CheckToolOption for RustCodeAnalysis {
             paths: opts.paths,
         };
 
-        match ConcurrentRunner::new(num_jobs, act_on_file)
+        let cache = cfg.cache.clone();
+        ConcurrentRunner::new(num_jobs, act_on_file)
             .set_proc_dir_paths(process_dir_path)
             .run(cfg, files_data)
-        {
-            Ok(_) => {}
-            Err(e) => {
-                log::info!("{:?}", e);
-                process::exit(1);
-            }
-        };
+            .map_err(|e| anyhow::anyhow!("RustCodeAnalysis run failed! {:?}", e))?;
+        if util::is_cancelled() {
+            return Err(util::InterruptError::Cancelled.into());
+        }
+        if let Some(c) = cache {
+            let (hits, misses) = c.stats();
+            log::info!(
+                "RustCodeAnalysis cache: {} files reused, {} analyzed",
+                hits,
+                misses
+            );
+        }
         Ok(())
     }
 
@@ -647,45 +911,96 @@ impl super::CheckToolOption for RustCodeAnalysis {
     /// for users and calculation.
     fn parse(&mut self) -> Result<()> {
         log::info!(
-            "RustCodeAnalysis parse: FUNC_SPACE_RESULT len: {:?}",
-            FUNC_SPACE_RESULT.lock().unwrap().len()
+            "RustCodeAnalysis parse: function spaces: {:?}",
+            self.spaces.lock().unwrap().len()
         );
         if let Some(config::QualityEvaluationConfig {
-            measeure_cfg: Some(m),
+            measure_cfg: Some(m),
             ..
         }) = &self.config.quality_evaluation_cfg
         {
-            let mut large_cyclomatic_complexity: u64 = 0;
-            let mut large_cognitive_complexity: u64 = 0;
-            let mut large_num_rows_file: u64 = 0;
-            let mut large_num_rows_function: u64 = 0;
-            let mut func_space = FUNC_SPACE_RESULT.lock().unwrap();
+            let mut thresholds = ThresholdResolver::new(m, &self.project_cfg.dir)?;
+            let mut suppressions = suppress::Suppressions::new(&self.project_cfg.dir);
+            for f in self.source_files.files.iter() {
+                suppressions.scan_file(f);
+            }
+            let check_item = config::QualityEvaluation::Measure.name();
+            let size = m.size_measure.unwrap_or_default();
+            let mut findings: Vec<Finding> = vec![];
+            let mut names = qualified::Resolver::new(
+                &self.context,
+                self.project_cfg.package.as_deref(),
+                &self.project_cfg.dir,
+                &self.source_files.modules,
+            );
+            let mut func_space = self.spaces.lock().unwrap();
             for v in func_space.iter_mut() {
-                if v.kind == FuncKind::Function {
-                    // for function
-                    let func_len = v.end_line - v.start_line;
-                    if func_len > m.large_num_rows_function.unwrap() as usize {
-                        large_num_rows_function += 1;
-                        v.if_large_function = true;
+                v.qualified = names.qualify(v);
+                v.qualified_name = v.qualified.to_string();
+                v.class = names.classify(v);
+                let excluded = m
+                    .classes
+                    .as_ref()
+                    .and_then(|c| c.get(v.class))
+                    .and_then(|c| c.exclude)
+                    .unwrap_or(false);
+                if excluded {
+                    v.excluded = true;
+                    self.func_info.push(v.clone());
+                    continue;
+                }
+                let t = thresholds.get(&v.file, v.class);
+                v.threshold_override = t.matched.clone();
+                let file = PathBuf::from(&v.file);
+                // A file space is only suppressed by `allow-file`.
+                let line = (v.kind == FuncKind::Function).then_some(v.start_line);
+                // A function is changed if a changed line is in its span, a file if any is.
+                v.changed = self.context.changes.as_ref().map(|c| match line {
+                    Some(start) => c.touches(&file, start, v.end_line),
+                    None => c.touches_file(&file),
+                });
+                for metric in suppress::MEASURE_METRICS {
+                    let (value, threshold) = match (v.metric(metric, size), t.values.get(metric)) {
+                        (Some(v), Some(t)) => (v, *t),
+                        _ => continue,
+                    };
+                    // e.g. the halstead volume of an empty function.
+                    if !value.is_finite() {
+                        continue;
                     }
-                    if v.cognitive > m.large_cognitive_complexity.unwrap() as usize {
-                        large_cognitive_complexity += 1;
-                        v.if_large_cognitive = true;
+                    let (past, ratio) = if lower_is_worse(metric) {
+                        (value < threshold, threshold / value.max(1.0))
+                    } else {
+                        (value > threshold, value / threshold.max(1.0))
+                    };
+                    if !past {
+                        continue;
                     }
-                    if v.cyclomatic > m.large_cyclomatic_complexity.unwrap() as usize {
-                        large_cyclomatic_complexity += 1;
-                        v.if_large_cyclomatic = true;
+                    if suppressions.suppress(&file, line, metric, check_item) {
+                        v.suppressed.push(metric.to_string());
+                        continue;
                     }
-                } else {
-                    // for the kind of file
-                    let file_len = v.end_line - v.start_line;
-                    if file_len > m.large_num_rows_file.unwrap() as usize {
-                        large_num_rows_file += 1;
-                        v.if_large_file = true;
+                    v.hotspot_score += (ratio - 1.0).max(0.0);
+                    v.mark(metric);
+                    if v.changed == Some(false) {
+                        self.untouched += 1;
+                        continue;
                     }
+                    findings.push(Finding {
+                        metric: *metric,
+                        ratio: ratio.max(1.0),
+                        class: v.class,
+                    });
                 }
                 self.func_info.push(v.clone());
             }
+            let count = |metric| findings.iter().filter(|f| f.metric == metric).count() as u64;
+            let large_cyclomatic_complexity = count(suppress::CYCLOMATIC_COMPLEXITY);
+            let large_cognitive_complexity = count(suppress::COGNITIVE_COMPLEXITY);
+            let large_num_rows_file = count(suppress::NUM_ROWS_FILE);
+            let large_num_rows_function = count(suppress::NUM_ROWS_FUNCTION);
+            self.suppressions = suppressions.into_list();
+            self.findings = findings;
             self.parse_result = Some(result::ParseResultDetail::CodeAnalysis {
                 large_cyclomatic_complexity: Some(large_cyclomatic_complexity),
                 large_cognitive_complexity: Some(large_cognitive_complexity),
@@ -698,41 +1013,74 @@ impl super::CheckToolOption for RustCodeAnalysis {
         Ok(())
     }
 
-    /// Calculate the measure score
+    /// Calculate the measure score.
+    /// Each finding costs the `*_score` of its metric, scaled by the penalty curve and capped.
     fn count(&mut self) -> Result<()> {
         log::info!("RustCodeAnalysis count: {:?}", self.parse_result);
         if let Some(config::QualityEvaluationConfig {
-            measeure_cfg:
-                Some(config::MeasureEvaluationConfig {
-                    large_cyclomatic_complexity_score: Some(large_cyclomatic_complexity_score_cfg),
-                    large_cognitive_complexity_score: Some(large_cognitive_complexity_score_cfg),
-                    large_num_rows_function_score: Some(large_num_rows_function_score_cfg),
-                    large_num_rows_file_score: Some(large_num_rows_file_score_cfg),
-                    measure_score: Some(measure_score_cfg),
-                    measure_weight: Some(measure_weight_cfg),
-                    ..
-                }),
+            measure_cfg: Some(m),
             ..
-        }) = self.config.quality_evaluation_cfg
+        }) = &self.config.quality_evaluation_cfg
         {
-            if let Some(result::ParseResultDetail::CodeAnalysis {
-                large_cyclomatic_complexity: Some(large_cyclomatic_complexity),
-                large_cognitive_complexity: Some(large_cognitive_complexity),
-                large_num_rows_file: Some(large_num_rows_file),
-                large_num_rows_function: Some(large_num_rows_function),
-            }) = &self.parse_result
+            if let (
+                Some(measure_score_cfg),
+                Some(measure_weight_cfg),
+                Some(result::ParseResultDetail::CodeAnalysis { .. }),
+            ) = (m.measure_score, m.measure_weight, &self.parse_result)
             {
-                let score = measure_score_cfg
-                    .saturating_sub(
-                        large_cyclomatic_complexity_score_cfg * large_cyclomatic_complexity,
-                    )
-                    .saturating_sub(
-                        large_cognitive_complexity_score_cfg * large_cognitive_complexity,
-                    )
-                    .saturating_sub(large_num_rows_file * large_num_rows_file_score_cfg)
-                    .saturating_sub(large_num_rows_function * large_num_rows_function_score_cfg)
-                    * 100
-                    / measure_score_cfg;
+                let penalty_cfg = m.penalty.clone().unwrap_or_default();
+                let classes = m.classes.clone().unwrap_or_default();
+                let weight = |c| classes.get(c).and_then(|c| c.weight).unwrap_or(100);
+                let mut class_penalty: HashMap<config::CodeClass, u64> = HashMap::new();
+                self.penalties.clear();
+                for metric in suppress::MEASURE_METRICS {
+                    let found: Vec<&Finding> = self
+                        .findings
+                        .iter()
+                        .filter(|f| f.metric == *metric)
+                        .collect();
+                    let configured = m.threshold(metric).is_some()
+                        || m.overrides
+                            .iter()
+                            .flatten()
+                            .any(|o| o.threshold(metric).is_some());
+                    if !configured && found.is_empty() {
+                        continue;
+                    }
+                    let score_cfg = m.score(metric).unwrap_or_default();
+                    let p = penalty_cfg.get(metric);
+                    let curve = p.curve.unwrap_or_default();
+                    let mut penalty = result::MetricPenalty {
+                        metric: metric.to_string(),
+                        curve,
+                        score: score_cfg,
+                        cap: p.cap,
+                        findings: found.len() as u64,
+                        penalty: 0,
+                    };
+                    for f in found {
+                        let v = curve.penalty(score_cfg, f.ratio);
+                        let v = p.cap.map_or(v, |c| v.min(c)) * weight(f.class) / 100;
+                        penalty.penalty += v;
+                        *class_penalty.entry(f.class).or_default() += v;
+                    }
+                    self.penalties.push(penalty);
+                }
+                self.classes = config::CodeClass::ALL
+                    .iter()
+                    .map(|c| result::ClassMeasure {
+                        class: *c,
+                        excluded: classes.get(*c).and_then(|c| c.exclude).unwrap_or(false),
+                        weight: weight(*c),
+                        files: self.class_count(*c, FuncKind::Unit),
+                        functions: self.class_count(*c, FuncKind::Function),
+                        findings: self.findings.iter().filter(|f| f.class == *c).count() as u64,
+                        penalty: class_penalty.get(c).copied().unwrap_or_default(),
+                    })
+                    .filter(|c| c.files + c.functions > 0)
+                    .collect();
+                let total: u64 = self.penalties.iter().map(|p| p.penalty).sum();
+                let score = measure_score_cfg.saturating_sub(total) * 100 / measure_score_cfg;
                 self.count_result = Some(result::CountResultDetail::CodeAnalysis {
                     score: Some(score),
                     normalized_score: Some(score * measure_weight_cfg / 100),
@@ -745,6 +1093,27 @@ impl super::CheckToolOption for RustCodeAnalysis {
     /// Process the results presented to the user
     fn result(&mut self, result: &mut result::Result) -> Result<()> {
         log::info!("RustCodeAnalysis result: {:?}", self.count_result);
+        result
+            .artifacts
+            .extend(self.artifacts(config::QualityEvaluation::Measure));
+        result.measured = Some(Arc::new(self.func_info.clone()));
+        result.add_suppressions(std::mem::take(&mut self.suppressions));
+        if let Some(changes) = &self.context.changes {
+            result.add_changes(
+                changes,
+                config::QualityEvaluation::Measure,
+                self.findings.len() as u64,
+                self.untouched,
+            );
+        }
+        let m = self
+            .config
+            .quality_evaluation_cfg
+            .as_ref()
+            .and_then(|q| q.measure_cfg.as_ref());
+        let size = m.and_then(|m| m.size_measure).unwrap_or_default();
+        let top = m.and_then(|m| m.top_hotspots).unwrap_or(10);
+        let measured: Vec<&FuncInfo> = self.func_info.iter().filter(|v| !v.excluded).collect();
         if let Some(result::CountResultDetail::CodeAnalysis {
             score: Some(score),
             normalized_score: Some(n_score),
@@ -753,6 +1122,14 @@ impl super::CheckToolOption for RustCodeAnalysis {
             result.code_measure = Some(result::CodeMeasure {
                 score: Some(score),
                 normalized_score: Some(n_score),
+                excluded_files: Some(self.excluded_files()),
+                sloc: Some(self.loc().sloc),
+                loc: Some(self.loc()),
+                penalties: Some(self.penalties.clone()),
+                classes: Some(self.classes.clone()),
+                distributions: Some(stats::distributions(&measured, size)),
+                hotspots: Some(stats::hotspots(&measured, top, &self.project_cfg.dir)),
+                modules: Some(stats::modules(&measured, size)),
             })
         }
 
@@ -761,17 +1138,151 @@ impl super::CheckToolOption for RustCodeAnalysis {
 }
 
 impl RustCodeAnalysis {
+    pub fn new(
+        project_cfg: config::ProjectInfoConfig,
+        config: config::Config,
+        context: Arc<context::ProjectContext>,
+    ) -> Self {
+        RustCodeAnalysis {
+            project_cfg,
+            config,
+            context,
+            source_files: Default::default(),
+            spaces: Default::default(),
+            func_info: vec![],
+            suppressions: vec![],
+            findings: vec![],
+            untouched: 0,
+            penalties: vec![],
+            classes: vec![],
+            check_result: None,
+            parse_result: None,
+            count_result: None,
+        }
+    }
+
+    /// The measure cache, `None` when disabled by `cache_cfg.enabled`.
+    fn measure_cache(&self) -> Result<Option<Arc<cache::MeasureCache>>> {
+        let c = self.config.cache_cfg.clone().unwrap_or_default();
+        if !c.enabled.unwrap_or(true) {
+            return Ok(None);
+        }
+        let dir = match c.dir {
+            Some(d) => self.context.workspace_root().join(d),
+            None => self
+                .context
+                .metadata
+                .target_directory
+                .clone()
+                .into_std_path_buf()
+                .join("cargo-quality")
+                .join("measure-cache"),
+        };
+        Ok(Some(Arc::new(cache::MeasureCache::new(&dir)?)))
+    }
+
+    /// The files written by the measure, listed under `check_item`.
+    pub fn artifacts(&self, check_item: config::QualityEvaluation) -> Vec<result::Artifact> {
+        [
+            (
+                self.project_cfg.result.clone(),
+                result::ArtifactFormat::Text,
+                "Metrics of each function and file, with the exceeded thresholds",
+            ),
+            (
+                self.excluded_files_path(),
+                result::ArtifactFormat::Toml,
+                "Files left out of the measure, by reason",
+            ),
+            (
+                self.details_path(),
+                result::ArtifactFormat::Directory,
+                "Output of rust-code-analysis for each source file",
+            ),
+        ]
+        .into_iter()
+        .map(|(path, format, purpose)| result::Artifact {
+            path,
+            check_item: Some(check_item.name().to_string()),
+            member: self.project_cfg.package.clone(),
+            format,
+            purpose: purpose.to_string(),
+        })
+        .collect()
+    }
+
+    /// Directory of the rust-code-analysis output of each source file.
+    fn details_path(&self) -> PathBuf {
+        self.project_cfg.result.with_file_name("details")
+    }
+
+    fn excluded_files_path(&self) -> PathBuf {
+        self.project_cfg
+            .result
+            .with_file_name("excluded_files.toml")
+    }
+
+    /// Lines of the measured files, summed over the file spaces.
+    fn loc(&self) -> result::LocSummary {
+        let mut loc = result::LocSummary::default();
+        for v in self.func_info.iter() {
+            if v.kind != FuncKind::Unit {
+                loc.functions += 1;
+                continue;
+            }
+            loc.files += 1;
+            loc.sloc += v.sloc as u64;
+            loc.ploc += v.ploc as u64;
+            loc.lloc += v.lloc as u64;
+            loc.cloc += v.cloc as u64;
+            loc.blank += v.blank as u64;
+        }
+        loc
+    }
+
+    /// Number of the spaces of the kind in the class of code.
+    fn class_count(&self, class: config::CodeClass, kind: FuncKind) -> u64 {
+        self.func_info
+            .iter()
+            .filter(|v| v.class == class && v.kind == kind)
+            .count() as u64
+    }
+
+    /// Count the files left out of the measure by reason.
+    fn excluded_files(&self) -> result::ExcludedFiles {
+        let s = &self.source_files;
+        result::ExcludedFiles {
+            ignore_file: s.excluded_count(util::ExcludeReason::IgnoreFile),
+            exclude_pattern: s.excluded_count(util::ExcludeReason::ExcludePattern),
+            generated_marker: s.excluded_count(util::ExcludeReason::GeneratedMarker),
+            generated_pattern: s.excluded_count(util::ExcludeReason::GeneratedPattern),
+        }
+    }
+
     fn write_result_file(&self) -> Result<()> {
-        let _ = File::create(&self.project_cfg.result).unwrap();
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.project_cfg.result)
-            .unwrap();
+        let mut file = File::create(&self.project_cfg.result).with_context(|| {
+            format!(
+                "Create measure result failed! {:?}",
+                self.project_cfg.result
+            )
+        })?;
         for v in self.func_info.iter() {
-            file.write_all(toml::to_string(v).unwrap().as_bytes())?;
+            let text = toml::to_string(v)
+                .with_context(|| format!("Serialize the measure of {} failed!", v.name))?;
+            file.write_all(text.as_bytes())?;
             file.write_all("\n".as_bytes())?;
         }
+
+        // List the excluded files and the reasons, for the user to review.
+        let excluded_path = self.excluded_files_path();
+        let excluded: BTreeMap<String, util::ExcludeReason> = self
+            .source_files
+            .excluded
+            .iter()
+            .map(|(k, v)| (k.display().to_string(), *v))
+            .collect();
+        std::fs::write(&excluded_path, toml::to_string(&excluded)?)
+            .with_context(|| format!("Write excluded files failed! {:?}", excluded_path))?;
         Ok(())
     }
 }
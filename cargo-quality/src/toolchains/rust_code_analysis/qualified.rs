@@ -0,0 +1,513 @@
+// Where the measured spaces are in the crate: their fully qualified name, e.g.
+// `my_crate::parser::Lexer::next_token`, and their class of code, e.g. test.
+//
+// rust-code-analysis gives the nesting of functions, closures, impls and traits in a file.
+// The crate and module come from the module tree of the target the file belongs to, as
+// walked by `util::get_target_files`, so files mounted with `#[path]` get the module they are
+// declared as. The class comes from the cargo target, and inline modules, the trait of an
+// impl and the test attributes from parsing the file with syn.
+
+use super::{FuncInfo, FuncKind};
+use crate::config::CodeClass;
+use crate::context;
+use crate::util;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+
+/// Structured name of a function or file space.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct QualifiedName {
+    #[serde(rename = "crate")]
+    pub krate: String,
+    /// Module path inside the crate.
+    pub module: Vec<String>,
+    /// Self type of the enclosing impl, without generics.
+    pub impl_type: Option<String>,
+    /// Trait of the enclosing impl, or the enclosing trait of a default method.
+    #[serde(rename = "trait")]
+    pub trait_name: Option<String>,
+    /// Names of the nested functions, outermost first. Empty for a file.
+    pub function: Vec<String>,
+    /// Index of the closure in its enclosing function.
+    pub closure: Option<usize>,
+}
+
+impl QualifiedName {
+    /// Module path with the crate, e.g. `my_crate::parser`.
+    pub fn module_path(&self) -> String {
+        std::iter::once(self.krate.as_str())
+            .chain(self.module.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+}
+
+impl std::fmt::Display for QualifiedName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![self.module_path()];
+        match (&self.impl_type, &self.trait_name) {
+            (Some(t), Some(tr)) => parts.push(format!("<{} as {}>", t, tr)),
+            (Some(t), None) | (None, Some(t)) => parts.push(t.clone()),
+            (None, None) => {}
+        }
+        parts.extend(self.function.iter().cloned());
+        if let Some(i) = self.closure {
+            parts.push(closure_name(i));
+        }
+        write!(f, "{}", parts.join("::"))
+    }
+}
+
+/// Name of the `i`th closure of a function, as rustc prints it.
+pub fn closure_name(i: usize) -> String {
+    format!("{{closure#{}}}", i)
+}
+
+/// An inline module, impl or test code of a file, with its lines.
+#[derive(Debug, Clone)]
+struct Scope {
+    start: usize,
+    end: usize,
+    kind: ScopeKind,
+}
+
+#[derive(Debug, Clone)]
+enum ScopeKind {
+    Module(String),
+    Impl {
+        trait_name: Option<String>,
+    },
+    /// A `#[cfg(test)]` module or a `#[test]` or `#[bench]` function.
+    Class(CodeClass),
+}
+
+/// The scopes of a file, outermost first.
+#[derive(Debug, Default)]
+struct FileScopes {
+    /// Class of the whole file, from `#![cfg(test)]`.
+    class: Option<CodeClass>,
+    scopes: Vec<Scope>,
+}
+
+/// A cargo target of the member: its root file, the dir of its modules and its crate name.
+#[derive(Debug)]
+struct Target {
+    root: PathBuf,
+    dir: PathBuf,
+    krate: String,
+    is_lib: bool,
+    class: CodeClass,
+}
+
+/// Resolve the qualified names and the classes of the spaces of a workspace member.
+#[derive(Debug)]
+pub struct Resolver {
+    package: String,
+    package_dir: PathBuf,
+    targets: Vec<Target>,
+    /// Crate and module path of the files of the module trees, see `util::SourceFiles`.
+    modules: BTreeMap<PathBuf, (String, Vec<String>)>,
+    scopes: HashMap<String, FileScopes>,
+}
+
+impl Resolver {
+    pub fn new(
+        ctx: &context::ProjectContext,
+        package: Option<&str>,
+        package_dir: &Path,
+        modules: &BTreeMap<PathBuf, (String, Vec<String>)>,
+    ) -> Self {
+        let mut targets: Vec<Target> = ctx
+            .targets
+            .iter()
+            .filter(|t| package.map_or(true, |p| t.package == p))
+            .map(|t| {
+                let root = t.src_path();
+                Target {
+                    dir: root.parent().map(Path::to_path_buf).unwrap_or_default(),
+                    root,
+                    krate: t.target.name.replace('-', "_"),
+                    is_lib: t.target.kind.iter().any(|k| k.ends_with("lib")),
+                    class: target_class(&t.target.kind),
+                }
+            })
+            .collect();
+        // The deepest module dir first, a library before the binaries sharing its dir.
+        targets.sort_by(|a, b| {
+            b.dir
+                .components()
+                .count()
+                .cmp(&a.dir.components().count())
+                .then(b.is_lib.cmp(&a.is_lib))
+        });
+        Resolver {
+            package: package.unwrap_or_default().replace('-', "_"),
+            package_dir: package_dir.to_path_buf(),
+            targets,
+            modules: modules.clone(),
+            scopes: HashMap::new(),
+        }
+    }
+
+    pub fn qualify(&mut self, v: &FuncInfo) -> QualifiedName {
+        let file = Path::new(&v.file);
+        let (krate, mut module) = self.module_of(file);
+        let enclosing = self.enclosing(v);
+        for s in enclosing.iter() {
+            if let ScopeKind::Module(m) = &s.kind {
+                module.push(m.clone());
+            }
+        }
+
+        let mut name = QualifiedName {
+            krate,
+            module,
+            ..Default::default()
+        };
+        if v.kind != FuncKind::Function {
+            return name;
+        }
+        for (kind, n) in v.scope.iter() {
+            match kind {
+                FuncKind::Impl => {
+                    name.impl_type = Some(type_name(n));
+                    name.trait_name = enclosing
+                        .iter()
+                        .rev()
+                        .find_map(|s| match &s.kind {
+                            ScopeKind::Impl { trait_name } => Some(trait_name.clone()),
+                            _ => None,
+                        })
+                        .flatten();
+                }
+                FuncKind::Trait => name.trait_name = Some(n.clone()),
+                FuncKind::Function => name.function.push(n.clone()),
+                _ => {}
+            }
+        }
+        match v.closure {
+            Some(i) => name.closure = Some(i),
+            None => name.function.push(v.name.clone()),
+        }
+        name
+    }
+
+    /// Class of the space: the class of its target, else of the file, else of the
+    /// innermost test module or function holding it.
+    pub fn classify(&mut self, v: &FuncInfo) -> CodeClass {
+        let file = Path::new(&v.file);
+        match self.target_of(file).map(|t| t.class) {
+            Some(CodeClass::Production) | None => {}
+            Some(c) => return c,
+        }
+        let stem = file.file_stem().and_then(|s| s.to_str());
+        if matches!(stem, Some("tests" | "test")) {
+            return CodeClass::Test;
+        }
+        if let Some(c) = self.file_scopes(&v.file).class {
+            return c;
+        }
+        if v.kind != FuncKind::Function {
+            return CodeClass::Production;
+        }
+        self.enclosing(v)
+            .iter()
+            .rev()
+            .find_map(|s| match s.kind {
+                ScopeKind::Class(c) => Some(c),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    fn file_scopes(&mut self, file: &str) -> &FileScopes {
+        self.scopes
+            .entry(file.to_string())
+            .or_insert_with(|| parse_scopes(Path::new(file)))
+    }
+
+    /// Scopes of the file holding the first line of the space.
+    fn enclosing(&mut self, v: &FuncInfo) -> Vec<Scope> {
+        self.file_scopes(&v.file)
+            .scopes
+            .iter()
+            .filter(|s| s.start <= v.start_line && v.start_line <= s.end)
+            .cloned()
+            .collect()
+    }
+
+    /// The target whose root is the file, else whose module dir holds it.
+    fn target_of(&self, file: &Path) -> Option<&Target> {
+        self.targets.iter().find(|t| t.root == file).or_else(|| {
+            self.targets
+                .iter()
+                .find(|t| !t.dir.as_os_str().is_empty() && file.starts_with(&t.dir))
+        })
+    }
+
+    /// Crate and module path of a file, from the module tree it was found in, else from the
+    /// target whose module dir holds it.
+    fn module_of(&self, file: &Path) -> (String, Vec<String>) {
+        if let Some(m) = self.modules.get(file) {
+            return m.clone();
+        }
+        match self.target_of(file) {
+            Some(t) if t.root == file => (t.krate.clone(), vec![]),
+            Some(t) => (
+                t.krate.clone(),
+                util::module_of_path(file.strip_prefix(&t.dir).unwrap_or(file)),
+            ),
+            None => (
+                self.package.clone(),
+                util::module_of_path(file.strip_prefix(&self.package_dir).unwrap_or(file)),
+            ),
+        }
+    }
+}
+
+/// Last path segment of a type, without generics, e.g. `Lexer` of `crate::Lexer<'a>`.
+fn type_name(ty: &str) -> String {
+    let ty = ty.trim().trim_start_matches('&').trim_start();
+    let ty = ty.strip_prefix("mut ").unwrap_or(ty);
+    let base = ty.split('<').next().unwrap_or(ty);
+    base.rsplit("::").next().unwrap_or(base).trim().to_string()
+}
+
+/// Class of the code of a target from its kinds, e.g. `test` or `custom-build`.
+fn target_class(kinds: &[String]) -> CodeClass {
+    let has = |k: &str| kinds.iter().any(|t| t == k);
+    if has("test") {
+        CodeClass::Test
+    } else if has("bench") {
+        CodeClass::Bench
+    } else if has("example") {
+        CodeClass::Example
+    } else if has("custom-build") {
+        CodeClass::BuildScript
+    } else {
+        CodeClass::Production
+    }
+}
+
+/// Class of the code an attribute marks: `#[cfg(test)]` or another predicate only true in
+/// test builds, `#[test]`, `#[tokio::test]` or `#[bench]`. `cfg(not(test))` marks production
+/// code.
+fn attr_class(attr: &syn::Attribute) -> Option<CodeClass> {
+    let last = attr.path.segments.last()?.ident.to_string();
+    match last.as_str() {
+        "test" => Some(CodeClass::Test),
+        "bench" => Some(CodeClass::Bench),
+        "cfg" => util::cfg::is_test_cfg(attr).then_some(CodeClass::Test),
+        _ => None,
+    }
+}
+
+fn attrs_class(attrs: &[syn::Attribute]) -> Option<CodeClass> {
+    attrs.iter().find_map(attr_class)
+}
+
+/// Inline modules, impls and test code of the file.
+fn parse_scopes(file: &Path) -> FileScopes {
+    let parsed = std::fs::read_to_string(file)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| Ok(syn::parse_file(&text)?));
+    match parsed {
+        Ok(f) => {
+            let mut out = vec![];
+            collect_scopes(&f.items, &mut out);
+            FileScopes {
+                class: attrs_class(&f.attrs),
+                scopes: out,
+            }
+        }
+        Err(e) => {
+            log::debug!("Parse {:?} for qualified names failed: {:?}", file, e);
+            FileScopes::default()
+        }
+    }
+}
+
+fn collect_scopes(items: &[syn::Item], out: &mut Vec<Scope>) {
+    for item in items {
+        let span = item.span();
+        let (start, end) = (span.start().line, span.end().line);
+        match item {
+            syn::Item::Mod(m) => {
+                if let Some(class) = attrs_class(&m.attrs) {
+                    out.push(Scope {
+                        start,
+                        end,
+                        kind: ScopeKind::Class(class),
+                    });
+                }
+                if let Some((_, content)) = &m.content {
+                    out.push(Scope {
+                        start,
+                        end,
+                        kind: ScopeKind::Module(m.ident.to_string()),
+                    });
+                    collect_scopes(content, out);
+                }
+            }
+            syn::Item::Impl(i) => out.push(Scope {
+                start,
+                end,
+                kind: ScopeKind::Impl {
+                    trait_name: i
+                        .trait_
+                        .as_ref()
+                        .and_then(|(_, p, _)| p.segments.last())
+                        .map(|s| s.ident.to_string()),
+                },
+            }),
+            syn::Item::Fn(f) => {
+                if let Some(class) = attrs_class(&f.attrs) {
+                    out.push(Scope {
+                        start,
+                        end,
+                        kind: ScopeKind::Class(class),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A resolver of the library `my_crate` rooted at `src/lib.rs` of `dir`, with the module
+    /// paths of the files found by the module walker.
+    fn resolver(dir: &Path, modules: &[(&str, &[&str])]) -> Resolver {
+        let src = dir.join("src");
+        Resolver {
+            package: "my_crate".to_string(),
+            package_dir: dir.to_path_buf(),
+            targets: vec![Target {
+                root: src.join("lib.rs"),
+                dir: src,
+                krate: "my_crate".to_string(),
+                is_lib: true,
+                class: CodeClass::Production,
+            }],
+            modules: modules
+                .iter()
+                .map(|(f, m)| {
+                    let m = m.iter().map(|s| s.to_string()).collect();
+                    (dir.join(f), ("my_crate".to_string(), m))
+                })
+                .collect(),
+            scopes: HashMap::new(),
+        }
+    }
+
+    fn write(dir: &Path, file: &str, text: &str) -> String {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, text).unwrap();
+        path.display().to_string()
+    }
+
+    fn function(file: &str, name: &str, line: usize, scope: &[(FuncKind, &str)]) -> FuncInfo {
+        FuncInfo {
+            file: file.to_string(),
+            name: name.to_string(),
+            start_line: line,
+            end_line: line,
+            kind: FuncKind::Function,
+            scope: scope.iter().map(|(k, n)| (*k, n.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn inherent_impl() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write(
+            dir.path(),
+            "src/parser/lexer.rs",
+            "pub struct Lexer<'a>(&'a str);\n\
+             impl<'a> Lexer<'a> {\n\
+             \x20   pub fn next_token(&mut self) {}\n\
+             }\n",
+        );
+        let mut r = resolver(dir.path(), &[]);
+        let v = function(&file, "next_token", 3, &[(FuncKind::Impl, "Lexer<'a>")]);
+        assert_eq!(
+            r.qualify(&v).to_string(),
+            "my_crate::parser::lexer::Lexer::next_token"
+        );
+    }
+
+    #[test]
+    fn trait_impl() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write(
+            dir.path(),
+            "src/lib.rs",
+            "struct Lexer;\n\
+             impl Iterator for Lexer {\n\
+             \x20   type Item = u8;\n\
+             \x20   fn next(&mut self) -> Option<u8> {\n\
+             \x20       None\n\
+             \x20   }\n\
+             }\n",
+        );
+        let mut r = resolver(dir.path(), &[]);
+        let v = function(&file, "next", 4, &[(FuncKind::Impl, "Lexer")]);
+        let name = r.qualify(&v);
+        assert_eq!(name.impl_type.as_deref(), Some("Lexer"));
+        assert_eq!(name.trait_name.as_deref(), Some("Iterator"));
+        assert_eq!(name.to_string(), "my_crate::<Lexer as Iterator>::next");
+    }
+
+    #[test]
+    fn nested_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write(
+            dir.path(),
+            "src/lib.rs",
+            "mod a {\n\
+             \x20   pub mod b {\n\
+             \x20       pub fn f() {}\n\
+             \x20   }\n\
+             }\n\
+             #[cfg(test)]\n\
+             mod tests {\n\
+             \x20   #[test]\n\
+             \x20   fn t() {}\n\
+             }\n",
+        );
+        let mut r = resolver(dir.path(), &[]);
+        let f = function(&file, "f", 3, &[]);
+        assert_eq!(r.qualify(&f).to_string(), "my_crate::a::b::f");
+        assert_eq!(r.classify(&f), CodeClass::Production);
+        let t = function(&file, "t", 9, &[]);
+        assert_eq!(r.qualify(&t).to_string(), "my_crate::tests::t");
+        assert_eq!(r.classify(&t), CodeClass::Test);
+    }
+
+    #[test]
+    fn mod_rs_and_flat_files() {
+        let dir = Path::new("/p");
+        let r = resolver(dir, &[]);
+        let module = |f: &str| r.module_of(&dir.join(f)).1.join("::");
+        assert_eq!(module("src/lib.rs"), "");
+        assert_eq!(module("src/parser/mod.rs"), "parser");
+        assert_eq!(module("src/parser.rs"), "parser");
+        assert_eq!(module("src/parser/lexer.rs"), "parser::lexer");
+    }
+
+    #[test]
+    fn path_attribute() {
+        let dir = Path::new("/p");
+        let r = resolver(dir, &[("src/generated/out.rs", &["tables"])]);
+        let (krate, module) = r.module_of(&dir.join("src/generated/out.rs"));
+        assert_eq!(krate, "my_crate");
+        assert_eq!(module, vec!["tables".to_string()]);
+    }
+}
@@ -0,0 +1,125 @@
+// Summaries of the measured functions and files for the result: the distribution of each
+// metric, the hotspot list and the aggregates of each module.
+
+use super::{FuncInfo, FuncKind};
+use crate::config;
+use crate::result;
+use crate::suppress;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Distribution of each metric over the spaces it applies to.
+pub fn distributions(
+    spaces: &[&FuncInfo],
+    size: config::SizeMeasure,
+) -> Vec<result::MetricDistribution> {
+    let mut out = vec![];
+    for metric in suppress::MEASURE_METRICS {
+        let mut values: Vec<f64> = spaces
+            .iter()
+            .filter_map(|v| v.metric(metric, size))
+            .filter(|v| v.is_finite())
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+        let max = values[values.len() - 1];
+        out.push(result::MetricDistribution {
+            metric: metric.to_string(),
+            count: values.len() as u64,
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            median: percentile(&values, 0.5),
+            p90: percentile(&values, 0.9),
+            p99: percentile(&values, 0.99),
+            max,
+            histogram: histogram(&values, max),
+        });
+    }
+    out
+}
+
+/// Nearest-rank percentile of the sorted values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Count the sorted values in the buckets `..=1`, `..=2`, `..=4` and so on up to the max.
+fn histogram(sorted: &[f64], max: f64) -> Vec<result::HistogramBucket> {
+    let mut out = vec![];
+    let mut le = 1.0;
+    let mut rest = sorted;
+    loop {
+        let n = rest.iter().take_while(|v| **v <= le).count();
+        out.push(result::HistogramBucket {
+            le,
+            count: n as u64,
+        });
+        rest = &rest[n..];
+        if le >= max {
+            break;
+        }
+        le *= 2.0;
+    }
+    out
+}
+
+/// The `top` functions and files with the highest hotspot score.
+pub fn hotspots(spaces: &[&FuncInfo], top: usize, root: &Path) -> Vec<result::Hotspot> {
+    let mut ranked: Vec<&&FuncInfo> = spaces.iter().filter(|v| v.hotspot_score > 0.0).collect();
+    ranked.sort_by(|a, b| b.hotspot_score.total_cmp(&a.hotspot_score));
+    ranked
+        .into_iter()
+        .take(top)
+        .map(|v| {
+            let file = Path::new(&v.file);
+            let file = file.strip_prefix(root).unwrap_or(file).to_path_buf();
+            result::Hotspot {
+                name: match v.kind {
+                    FuncKind::Function => v.qualified_name.clone(),
+                    _ => file.display().to_string(),
+                },
+                file,
+                line: v.start_line,
+                class: v.class,
+                score: v.hotspot_score,
+                exceeded: v.exceeded.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Aggregates of each module, the one with the highest hotspot score first.
+pub fn modules(spaces: &[&FuncInfo], size: config::SizeMeasure) -> Vec<result::ModuleMeasure> {
+    let mut modules: BTreeMap<String, result::ModuleMeasure> = BTreeMap::new();
+    for v in spaces {
+        let module = v.qualified.module_path();
+        let m = modules
+            .entry(module.clone())
+            .or_insert_with(|| result::ModuleMeasure {
+                module,
+                ..Default::default()
+            });
+        if !v.exceeded.is_empty() {
+            m.findings += 1;
+        }
+        m.hotspot_score += v.hotspot_score;
+        if v.kind == FuncKind::Function {
+            m.functions += 1;
+            m.mean_cyclomatic += v.cyclomatic as f64;
+            m.mean_cognitive += v.cognitive as f64;
+            m.max_cognitive = m.max_cognitive.max(v.cognitive as u64);
+        } else {
+            m.files += 1;
+            m.lines += v.size(size) as u64;
+        }
+    }
+    let mut out: Vec<result::ModuleMeasure> = modules.into_values().collect();
+    for m in out.iter_mut().filter(|m| m.functions > 0) {
+        m.mean_cyclomatic /= m.functions as f64;
+        m.mean_cognitive /= m.functions as f64;
+    }
+    out.sort_by(|a, b| b.hotspot_score.total_cmp(&a.hotspot_score));
+    out
+}
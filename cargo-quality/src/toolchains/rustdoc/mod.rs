@@ -0,0 +1,188 @@
+// Rustdoc diagnostics of the library docs, from `cargo doc --no-deps --message-format=json`.
+//
+// Only the `rustdoc::` lints are kept, compiler warnings are left to the static check. The docs
+// fail to build when `build-finished` reports a failure or is missing, e.g. after a crash.
+
+use super::CheckToolOption;
+use crate::config;
+use crate::context;
+use crate::result;
+use crate::util;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Prefix of the rustdoc lint codes, e.g. `rustdoc::bare_urls`.
+const RUSTDOC_LINT_PREFIX: &str = "rustdoc::";
+
+/// A line of the JSON output of cargo.
+#[derive(Debug, Deserialize)]
+struct Message {
+    reason: String,
+    /// Of `build-finished`.
+    success: Option<bool>,
+    /// Of `compiler-message`.
+    message: Option<Diagnostic>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Diagnostic {
+    message: String,
+    level: String,
+    code: Option<DiagnosticCode>,
+    spans: Vec<DiagnosticSpan>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiagnosticCode {
+    code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiagnosticSpan {
+    /// Relative to the workspace root.
+    file_name: String,
+    line_start: usize,
+    is_primary: bool,
+}
+
+/// A rustdoc diagnostic.
+#[derive(Debug, Clone, Serialize)]
+pub struct DocFinding {
+    /// Lint name without the `rustdoc::` prefix, e.g. `broken_intra_doc_links`.
+    pub lint: String,
+    /// `warning` or `error`.
+    pub level: String,
+    pub message: String,
+    /// Relative to the package dir.
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+/// Rustdoc check struct
+#[derive(Debug)]
+pub struct Rustdoc {
+    pub project_cfg: config::ProjectInfoConfig,
+    pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
+    /// Whether the docs built, `None` until parsed.
+    pub success: Option<bool>,
+    pub findings: Vec<DocFinding>,
+}
+
+impl Rustdoc {
+    pub fn new(
+        project_cfg: config::ProjectInfoConfig,
+        config: config::Config,
+        context: Arc<context::ProjectContext>,
+    ) -> Self {
+        Rustdoc {
+            project_cfg,
+            config,
+            context,
+            success: None,
+            findings: vec![],
+        }
+    }
+
+    /// Number of the findings of the lint.
+    pub fn count_of(&self, lint: &str) -> u64 {
+        self.findings.iter().filter(|f| f.lint == lint).count() as u64
+    }
+
+    fn finding(&self, d: Diagnostic) -> Option<DocFinding> {
+        let lint = d.code?.code.strip_prefix(RUSTDOC_LINT_PREFIX)?.to_string();
+        let span = d.spans.iter().find(|s| s.is_primary).or(d.spans.first());
+        let (file, line) = match span {
+            Some(s) => {
+                let file = self.context.workspace_root().join(&s.file_name);
+                let file = file
+                    .strip_prefix(&self.project_cfg.dir)
+                    .map(|f| f.to_path_buf())
+                    .unwrap_or(file);
+                (file, s.line_start)
+            }
+            None => (PathBuf::new(), 0),
+        };
+        Some(DocFinding {
+            lint,
+            level: d.level,
+            message: d.message,
+            file,
+            line,
+        })
+    }
+}
+
+impl CheckToolOption for Rustdoc {
+    /// Build the docs of the package, its dependencies left out.
+    fn check(&mut self) -> Result<()> {
+        log::info!("Rustdoc check: {:?}", self.project_cfg);
+        if let Err(e) = util::cargo_with_args(
+            "doc",
+            &["--no-deps", "--message-format=json"],
+            &self.project_cfg.manifest,
+            &self.project_cfg.result,
+            util::StdOutput::Out,
+            self.config.command_timeout(),
+        ) {
+            // A killed cargo leaves an incomplete output, don't score it.
+            if e.downcast_ref::<util::InterruptError>().is_some() {
+                return Err(e);
+            }
+            log::info!("Rustdoc check failed: {:?}", e);
+        }
+        Ok(())
+    }
+
+    /// Read the rustdoc diagnostics and the build status.
+    fn parse(&mut self) -> Result<()> {
+        let path = &self.project_cfg.result;
+        let f = File::open(path)
+            .with_context(|| format!("Read cargo doc output failed! {:?}", path))?;
+        let mut success = false;
+        let mut findings = vec![];
+        for l in BufReader::new(f).lines().flatten() {
+            let m: Message = match serde_json::from_str(&l) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            match m.reason.as_str() {
+                "build-finished" => success = m.success.unwrap_or(false),
+                "compiler-message" => {
+                    if let Some(f) = m.message.and_then(|d| self.finding(d)) {
+                        findings.push(f);
+                    }
+                }
+                _ => {}
+            }
+        }
+        log::info!(
+            "Rustdoc parse: {} findings, docs built: {}",
+            findings.len(),
+            success
+        );
+        self.success = Some(success);
+        self.findings = findings;
+        Ok(())
+    }
+
+    /// Scored by the doc check with the doc coverage.
+    fn count(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn result(&mut self, result: &mut result::Result) -> Result<()> {
+        result.artifacts.push(result::Artifact {
+            path: self.project_cfg.result.clone(),
+            check_item: Some(config::QualityEvaluation::Doc.name().to_string()),
+            member: self.project_cfg.package.clone(),
+            format: result::ArtifactFormat::Json,
+            purpose: "JSON messages of `cargo doc --no-deps`".to_string(),
+        });
+        Ok(())
+    }
+}
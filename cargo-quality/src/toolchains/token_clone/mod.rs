@@ -0,0 +1,479 @@
+// Token-based clone detection: blocks of at least `min_tokens` tokens found more than once in
+// the sources of the member, with identifiers and literals normalized so renamed copies match.
+//
+// Each window of `min_tokens` tokens is hashed; the windows equal to an earlier one are
+// extended to the longest common block. `use` declarations and attributes are left out, as
+// they repeat by nature.
+
+use super::CheckToolOption;
+use crate::config;
+use crate::context;
+use crate::result;
+use crate::util;
+use anyhow::{Context, Result};
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Keywords keep their text, the other identifiers are normalized.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "type", "unsafe", "use",
+    "where", "while",
+];
+
+/// Base of the rolling hash of the windows.
+const HASH_BASE: u64 = 1_000_003;
+
+/// A normalized token and its line.
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    id: u32,
+    line: usize,
+}
+
+/// Normalized tokens of a source file.
+#[derive(Debug)]
+pub struct SourceTokens {
+    file: PathBuf,
+    tokens: Vec<Token>,
+}
+
+/// Ids of the normalized tokens.
+#[derive(Debug, Default)]
+struct Interner(HashMap<String, u32>);
+
+impl Interner {
+    fn push(&mut self, out: &mut Vec<Token>, text: &str, span: Span) {
+        let next = self.0.len() as u32;
+        let id = *self.0.entry(text.to_string()).or_insert(next);
+        out.push(Token {
+            id,
+            line: span.start().line,
+        });
+    }
+
+    fn tokenize(&mut self, stream: TokenStream, out: &mut Vec<Token>) {
+        let mut iter = stream.into_iter().peekable();
+        while let Some(tt) = iter.next() {
+            match tt {
+                // A `use` declaration, up to its `;`.
+                TokenTree::Ident(i) if i == "use" => {
+                    for t in iter.by_ref() {
+                        if matches!(&t, TokenTree::Punct(p) if p.as_char() == ';') {
+                            break;
+                        }
+                    }
+                }
+                TokenTree::Ident(i) => {
+                    let text = i.to_string();
+                    let text = match text.as_str() {
+                        "true" | "false" => "$lit",
+                        k if KEYWORDS.contains(&k) => k,
+                        _ => "$id",
+                    };
+                    self.push(out, text, i.span());
+                }
+                // An attribute, `#[...]` or `#![...]`, doc comments included.
+                TokenTree::Punct(p) if p.as_char() == '#' => {
+                    let inner =
+                        matches!(iter.peek(), Some(TokenTree::Punct(b)) if b.as_char() == '!');
+                    let bang = if inner { iter.next() } else { None };
+                    match iter.peek() {
+                        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
+                            iter.next();
+                        }
+                        _ => {
+                            self.push(out, "#", p.span());
+                            if let Some(b) = bang {
+                                self.push(out, "!", b.span());
+                            }
+                        }
+                    }
+                }
+                TokenTree::Punct(p) => self.push(out, &p.as_char().to_string(), p.span()),
+                TokenTree::Literal(l) => self.push(out, "$lit", l.span()),
+                TokenTree::Group(g) => {
+                    let (open, close) = match g.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::None => ("", ""),
+                    };
+                    if !open.is_empty() {
+                        self.push(out, open, g.span_open());
+                    }
+                    self.tokenize(g.stream(), out);
+                    if !close.is_empty() {
+                        self.push(out, close, g.span_close());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A block found twice: the token index of each copy, in the concatenated tokens, and its length.
+#[derive(Debug, Clone, Copy)]
+struct Duplicate {
+    first: usize,
+    second: usize,
+    len: usize,
+}
+
+/// The duplicated blocks of at least `min` tokens, within a file or across files.
+/// `bounds` gives the token range of each file in `ids`, in order.
+///
+/// The windows are scanned in order and a window equal to an earlier one is extended to the
+/// longest common block. The windows starting inside a reported copy are skipped, so every
+/// token is extended over once and a periodic run, e.g. a literal table, is a single block.
+fn find_duplicates(ids: &[u32], bounds: &[(usize, usize)], min: usize) -> Vec<Duplicate> {
+    let pow = (1..min).fold(1u64, |p, _| p.wrapping_mul(HASH_BASE));
+    let file_of = |i: usize| bounds.partition_point(|(_, e)| *e <= i);
+    // The first window of each hash.
+    let mut first_of: HashMap<u64, usize> = HashMap::new();
+    // End of the last reported copy.
+    let mut reported = 0;
+    let mut out = vec![];
+    for &(start, end) in bounds.iter().filter(|(s, e)| e - s >= min) {
+        let mut hash = ids[start..start + min].iter().fold(0u64, |h, id| {
+            h.wrapping_mul(HASH_BASE).wrapping_add(*id as u64)
+        });
+        for i in start..=end - min {
+            if i > start {
+                hash = hash
+                    .wrapping_sub((ids[i - 1] as u64).wrapping_mul(pow))
+                    .wrapping_mul(HASH_BASE)
+                    .wrapping_add(ids[i + min - 1] as u64);
+            }
+            let f = *first_of.entry(hash).or_insert(i);
+            if f == i || i < reported || ids[f..f + min] != ids[i..i + min] {
+                continue;
+            }
+            let fa = bounds[file_of(f)];
+            // Extend the pair to the longest common block.
+            let (mut a, mut b) = (f, i);
+            while a > fa.0 && b > start.max(reported) && ids[a - 1] == ids[b - 1] {
+                a -= 1;
+                b -= 1;
+            }
+            let mut len = min + (i - b);
+            while a + len < fa.1 && b + len < end && ids[a + len] == ids[b + len] {
+                len += 1;
+            }
+            reported = b + len;
+            if fa.0 == start && b < a + len {
+                // A run repeated right after itself: [a, b + len) has the period b - a, split
+                // it into two halves of a whole number of periods.
+                let period = b - a;
+                let half = (b + len - a) / 2 / period * period;
+                if half >= min {
+                    out.push(Duplicate {
+                        first: a,
+                        second: a + half,
+                        len: half,
+                    });
+                }
+            } else {
+                out.push(Duplicate {
+                    first: a,
+                    second: b,
+                    len,
+                });
+            }
+        }
+    }
+    out
+}
+
+/// The detailed result, written to `duplication_check.toml`.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    files: &'a [result::FileDuplication],
+    blocks: &'a [result::DuplicatedBlock],
+}
+
+/// Token clone check struct
+#[derive(Debug)]
+pub struct TokenClone {
+    pub project_cfg: config::ProjectInfoConfig,
+    pub config: config::Config,
+    pub context: Arc<context::ProjectContext>,
+    pub sources: Vec<SourceTokens>,
+    /// Duplicated blocks, the largest first.
+    pub blocks: Vec<result::DuplicatedBlock>,
+    /// Every measured file, the highest duplication first.
+    pub files: Vec<result::FileDuplication>,
+    pub count_result: Option<result::CountResultDetail>,
+}
+
+impl TokenClone {
+    fn duplication_cfg(&self) -> config::DuplicationEvaluationConfig {
+        self.config
+            .quality_evaluation_cfg
+            .as_ref()
+            .and_then(|q| q.duplication_cfg.clone())
+            .unwrap_or_default()
+    }
+
+    fn relative(&self, file: &Path) -> PathBuf {
+        file.strip_prefix(&self.project_cfg.dir)
+            .unwrap_or(file)
+            .to_path_buf()
+    }
+
+    /// Duplicated lines over all the files, in percent.
+    fn percentage(&self) -> f64 {
+        let lines: u64 = self.files.iter().map(|f| f.lines).sum();
+        let duplicated: u64 = self.files.iter().map(|f| f.duplicated_lines).sum();
+        percent(duplicated, lines)
+    }
+
+    fn write_result_file(&self) -> Result<()> {
+        let path = &self.project_cfg.result;
+        let report = Report {
+            files: &self.files,
+            blocks: &self.blocks,
+        };
+        std::fs::write(path, toml::to_string(&report)?)
+            .with_context(|| format!("Write duplication result failed! {:?}", path))
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => part as f64 * 100.0 / total as f64,
+    }
+}
+
+impl CheckToolOption for TokenClone {
+    /// Tokenize the source files of the member.
+    fn check(&mut self) -> Result<()> {
+        log::info!("TokenClone check: {:?}", self.project_cfg);
+        let exclude = util::make_globset(&self.config.exclude_dir.clone().unwrap_or_default())?;
+        let source_files = util::get_target_files(
+            &self.context,
+            &self.config.source_cfg.clone().unwrap_or_default(),
+            self.project_cfg.package.as_deref(),
+            &self.project_cfg.dir,
+            &exclude,
+        )?;
+        let mut interner = Interner::default();
+        for file in source_files.files.iter() {
+            if util::is_cancelled() {
+                return Err(util::InterruptError::Cancelled.into());
+            }
+            let source = std::fs::read_to_string(file)
+                .with_context(|| format!("Read source file failed! {:?}", file))?;
+            let stream = match TokenStream::from_str(&source) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::warn!("TokenClone: skip {:?}, it can't be tokenized: {}", file, e);
+                    continue;
+                }
+            };
+            let mut tokens = vec![];
+            interner.tokenize(stream, &mut tokens);
+            self.sources.push(SourceTokens {
+                file: file.clone(),
+                tokens,
+            });
+        }
+        Ok(())
+    }
+
+    /// Find the duplicated blocks and the duplicated lines of each file.
+    fn parse(&mut self) -> Result<()> {
+        let cfg = self.duplication_cfg();
+        let min = cfg.min_tokens.unwrap_or(50).max(1);
+        let mut ids = vec![];
+        let mut bounds = vec![];
+        for s in self.sources.iter() {
+            let start = ids.len();
+            ids.extend(s.tokens.iter().map(|t| t.id));
+            bounds.push((start, ids.len()));
+        }
+        let duplicates = find_duplicates(&ids, &bounds, min);
+
+        let locate = |i: usize| {
+            let f = bounds.partition_point(|(_, e)| *e <= i);
+            (f, &self.sources[f].tokens[i - bounds[f].0])
+        };
+        let span = |i: usize, len: usize| {
+            let ((f, first), (_, last)) = (locate(i), locate(i + len - 1));
+            (f, first.line, last.line)
+        };
+        let mut duplicated: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); self.sources.len()];
+        let mut blocks = vec![];
+        for d in duplicates.iter() {
+            let copies = [span(d.first, d.len), span(d.second, d.len)];
+            for (f, start, end) in copies {
+                duplicated[f].extend(start..=end);
+            }
+            let [first, second] = copies.map(|(f, start_line, end_line)| result::CodeSpan {
+                file: self.relative(&self.sources[f].file),
+                start_line,
+                end_line,
+            });
+            blocks.push(result::DuplicatedBlock {
+                tokens: d.len as u64,
+                first,
+                second,
+            });
+        }
+        blocks.sort_by(|a, b| b.tokens.cmp(&a.tokens));
+
+        let mut files = vec![];
+        for (s, duplicated) in self.sources.iter().zip(duplicated.iter()) {
+            let lines: BTreeSet<usize> = s.tokens.iter().map(|t| t.line).collect();
+            let duplicated = lines.intersection(duplicated).count() as u64;
+            files.push(result::FileDuplication {
+                file: self.relative(&s.file),
+                lines: lines.len() as u64,
+                duplicated_lines: duplicated,
+                percentage: percent(duplicated, lines.len() as u64),
+            });
+        }
+        files.sort_by(|a, b| b.percentage.total_cmp(&a.percentage));
+        self.blocks = blocks;
+        self.files = files;
+        log::info!(
+            "TokenClone parse: {} blocks, {:.1}% duplicated",
+            self.blocks.len(),
+            self.percentage()
+        );
+        self.write_result_file()?;
+        Ok(())
+    }
+
+    /// Each percent of duplicated lines costs `duplicated_percent_score`.
+    fn count(&mut self) -> Result<()> {
+        let cfg = self.duplication_cfg();
+        if let config::DuplicationEvaluationConfig {
+            duplicated_percent_score: Some(percent_score),
+            duplication_score: Some(duplication_score),
+            duplication_weight: Some(duplication_weight),
+            ..
+        } = cfg
+        {
+            let penalty = self.percentage().round() as u64 * percent_score;
+            let score = duplication_score.saturating_sub(penalty) * 100 / duplication_score;
+            self.count_result = Some(result::CountResultDetail::Duplication {
+                score: Some(score),
+                normalized_score: Some(score * duplication_weight / 100),
+            });
+        }
+        Ok(())
+    }
+
+    fn result(&mut self, result: &mut result::Result) -> Result<()> {
+        log::info!("TokenClone result: {:?}", self.count_result);
+        let cfg = self.duplication_cfg();
+        result.artifacts.push(result::Artifact {
+            path: self.project_cfg.result.clone(),
+            check_item: Some(config::QualityEvaluation::Duplication.name().to_string()),
+            member: self.project_cfg.package.clone(),
+            format: result::ArtifactFormat::Toml,
+            purpose: "Duplicated lines of each file and every duplicated block".to_string(),
+        });
+        let (score, normalized_score) = match self.count_result {
+            Some(result::CountResultDetail::Duplication {
+                score,
+                normalized_score,
+            }) => (score, normalized_score),
+            _ => (None, None),
+        };
+        let lines: u64 = self.files.iter().map(|f| f.lines).sum();
+        let duplicated_lines: u64 = self.files.iter().map(|f| f.duplicated_lines).sum();
+        result.code_duplication = Some(result::CodeDuplication {
+            score,
+            normalized_score,
+            min_tokens: cfg.min_tokens.unwrap_or(50) as u64,
+            files: self.files.len() as u64,
+            lines,
+            duplicated_lines,
+            percentage: self.percentage(),
+            block_count: self.blocks.len() as u64,
+            blocks: self
+                .blocks
+                .iter()
+                .take(cfg.top_blocks.unwrap_or(10))
+                .cloned()
+                .collect(),
+            file_duplication: self
+                .files
+                .iter()
+                .filter(|f| f.duplicated_lines > 0)
+                .cloned()
+                .collect(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(files: &[&[u32]], min: usize) -> Vec<(usize, usize, usize)> {
+        let mut ids = vec![];
+        let mut bounds = vec![];
+        for f in files {
+            bounds.push((ids.len(), ids.len() + f.len()));
+            ids.extend_from_slice(f);
+        }
+        find_duplicates(&ids, &bounds, min)
+            .iter()
+            .map(|d| (d.first, d.second, d.len))
+            .collect()
+    }
+
+    #[test]
+    fn copy_across_files() {
+        let a: &[u32] = &[9, 1, 2, 3, 4, 5, 8];
+        let b: &[u32] = &[7, 1, 2, 3, 4, 5, 6];
+        assert_eq!(blocks(&[a, b], 3), vec![(1, 8, 5)]);
+    }
+
+    #[test]
+    fn copy_within_file() {
+        let a: &[u32] = &[1, 2, 3, 4, 0, 1, 2, 3, 4];
+        assert_eq!(blocks(&[a], 4), vec![(0, 5, 4)]);
+    }
+
+    #[test]
+    fn shorter_than_min() {
+        let a: &[u32] = &[1, 2, 3, 0, 1, 2, 3];
+        assert!(blocks(&[a], 4).is_empty());
+    }
+
+    #[test]
+    fn no_block_across_file_boundary() {
+        let a: &[u32] = &[1, 2];
+        let b: &[u32] = &[3, 4, 1, 2, 3, 4];
+        assert!(blocks(&[a, b], 4).is_empty());
+    }
+
+    #[test]
+    fn third_copy() {
+        let a: &[u32] = &[1, 2, 3, 4];
+        assert_eq!(blocks(&[a, a, a], 4), vec![(0, 4, 4), (0, 8, 4)]);
+    }
+
+    #[test]
+    fn periodic_run_is_one_block() {
+        let a: Vec<u32> = (0..10_000).map(|i| i % 3).collect();
+        assert_eq!(blocks(&[&a], 10), vec![(0, 4_998, 4_998)]);
+    }
+
+    #[test]
+    fn repeated_token_is_one_block() {
+        let a = vec![7; 101];
+        assert_eq!(blocks(&[&a], 5), vec![(0, 50, 50)]);
+    }
+}
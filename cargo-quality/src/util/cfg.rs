@@ -0,0 +1,115 @@
+// Evaluation of `cfg` predicates, to tell the code only built by `cargo test`.
+//
+// A predicate is evaluated with `test` unset and the other options unknown: `all`, `any` and
+// `not` follow three-valued logic, so `cfg(all(test, not(miri)))` is test code while
+// `cfg(feature = "test-utils")` and `cfg(any(test, feature = "x"))` are not.
+
+use syn::{Meta, NestedMeta};
+
+/// Value of the predicate with `test` set to `test`, `None` when it depends on other options.
+fn eval(predicate: &NestedMeta, test: bool) -> Option<bool> {
+    let meta = match predicate {
+        NestedMeta::Meta(m) => m,
+        NestedMeta::Lit(_) => return None,
+    };
+    match meta {
+        Meta::Path(p) if p.is_ident("test") => Some(test),
+        Meta::List(l) if l.path.is_ident("not") && l.nested.len() == 1 => {
+            eval(&l.nested[0], test).map(|v| !v)
+        }
+        Meta::List(l) if l.path.is_ident("all") => {
+            let values: Vec<Option<bool>> = l.nested.iter().map(|p| eval(p, test)).collect();
+            if values.contains(&Some(false)) {
+                Some(false)
+            } else if values.iter().all(|v| *v == Some(true)) {
+                Some(true)
+            } else {
+                None
+            }
+        }
+        Meta::List(l) if l.path.is_ident("any") => {
+            let values: Vec<Option<bool>> = l.nested.iter().map(|p| eval(p, test)).collect();
+            if values.contains(&Some(true)) {
+                Some(true)
+            } else if values.iter().all(|v| *v == Some(false)) {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether the predicate only holds in test builds, i.e. is false whenever `test` is unset.
+pub fn is_test_only(predicate: &NestedMeta) -> bool {
+    eval(predicate, false) == Some(false)
+}
+
+/// Whether the attribute is a `#[cfg(..)]` which only holds in test builds.
+pub fn is_test_cfg(attr: &syn::Attribute) -> bool {
+    if !attr.path.is_ident("cfg") {
+        return false;
+    }
+    match attr.parse_meta() {
+        Ok(Meta::List(l)) if l.nested.len() == 1 => is_test_only(&l.nested[0]),
+        _ => false,
+    }
+}
+
+/// The attributes applied by the attribute outside of test builds: itself, or the
+/// attributes of a `#[cfg_attr(..)]` whose predicate may hold without `test`.
+pub fn applied_metas(attr: &syn::Attribute) -> Vec<Meta> {
+    match attr.parse_meta() {
+        Ok(Meta::List(l)) if l.path.is_ident("cfg_attr") => {
+            let mut nested = l.nested.into_iter();
+            match nested.next() {
+                Some(p) if !is_test_only(&p) => nested
+                    .filter_map(|n| match n {
+                        NestedMeta::Meta(m) => Some(m),
+                        NestedMeta::Lit(_) => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            }
+        }
+        Ok(m) => vec![m],
+        Err(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(text: &str) -> syn::Attribute {
+        let item: syn::ItemStruct = syn::parse_str(&format!("{} struct S;", text)).unwrap();
+        item.attrs.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn test_cfg() {
+        assert!(is_test_cfg(&attr("#[cfg(test)]")));
+        assert!(is_test_cfg(&attr("#[cfg(all(test, not(miri)))]")));
+        assert!(is_test_cfg(&attr("#[cfg(all(unix, test))]")));
+        assert!(is_test_cfg(&attr("#[cfg(any(test, all(test, unix)))]")));
+    }
+
+    #[test]
+    fn not_test_cfg() {
+        assert!(!is_test_cfg(&attr("#[cfg(not(test))]")));
+        assert!(!is_test_cfg(&attr("#[cfg(feature = \"test-utils\")]")));
+        assert!(!is_test_cfg(&attr("#[cfg(any(test, feature = \"x\"))]")));
+        assert!(!is_test_cfg(&attr("#[cfg(not(all(test, unix)))]")));
+        assert!(!is_test_cfg(&attr("#[cfg_attr(test, derive(Debug))]")));
+    }
+
+    #[test]
+    fn cfg_attr_metas() {
+        let metas = applied_metas(&attr("#[cfg_attr(not(test), deny(missing_docs))]"));
+        assert_eq!(metas.len(), 1);
+        assert!(metas[0].path().is_ident("deny"));
+        assert!(applied_metas(&attr("#[cfg_attr(test, deny(missing_docs))]")).is_empty());
+        assert_eq!(applied_metas(&attr("#[deny(missing_docs)]")).len(), 1);
+    }
+}
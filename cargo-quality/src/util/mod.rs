@@ -1,41 +1,369 @@
+use crate::config;
+use crate::context;
 use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
 use log;
-use std::collections::HashMap;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::Command;
-use walkdir::{DirEntry, WalkDir};
-
-/// Get the specified file under the path.
-pub fn dir_and_name(d: &DirEntry, name: &str, exclude: Vec<String>) -> bool {
-    if exclude
-        .iter()
-        .any(|name| d.path().as_os_str().to_str().unwrap().contains(name))
-    {
-        return false;
-    }
-    return d.file_type().is_dir() && d.path().as_os_str().to_str().unwrap().ends_with(name);
-}
-
-/// Get all subdirectories under the path.
-pub fn get_all_dir(
-    p: PathBuf,
-    name: &str,
-    exclude: Vec<String>,
-    op: fn(e: &DirEntry, name: &str, exclude: Vec<String>) -> bool,
-) -> Result<HashMap<PathBuf, DirEntry>> {
-    let mut dir_result = HashMap::new();
-    for e in WalkDir::new(p)
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+pub mod cfg;
+
+/// Set when the user interrupts the evaluation with Ctrl-C.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Token of the check item running on the thread.
+    static CURRENT_TOKEN: RefCell<CancelToken> = RefCell::new(CancelToken::default());
+}
+
+/// Interval for polling running child processes and check items.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Build a glob set from the patterns. An invalid pattern is an error.
+pub fn make_globset(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for p in patterns.iter().filter(|p| !p.is_empty()) {
+        builder.add(Glob::new(p).with_context(|| format!("Invalid glob pattern: {:?}", p))?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Whether the path, relative to `root`, matches one of the globs.
+pub fn glob_match(globs: &GlobSet, root: &Path, path: &Path) -> bool {
+    globs.is_match(path.strip_prefix(root).unwrap_or(path))
+}
+
+/// Why a source file is left out of the measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExcludeReason {
+    /// Matched by a `.gitignore` or `.ignore` file.
+    IgnoreFile,
+    /// Matched by `exclude_dir`.
+    ExcludePattern,
+    /// Has an `@generated` marker in its header.
+    GeneratedMarker,
+    /// Matched by `source_cfg.generated_files`.
+    GeneratedPattern,
+}
+
+/// Source files found by `get_target_files`.
+#[derive(Debug, Clone, Default)]
+pub struct SourceFiles {
+    pub files: BTreeSet<PathBuf>,
+    pub excluded: BTreeMap<PathBuf, ExcludeReason>,
+    /// Crate and module path of each file, in the module tree of the first target reaching it.
+    pub modules: BTreeMap<PathBuf, (String, Vec<String>)>,
+}
+
+impl SourceFiles {
+    /// Number of files excluded for the reason.
+    pub fn excluded_count(&self, reason: ExcludeReason) -> u64 {
+        self.excluded.values().filter(|r| **r == reason).count() as u64
+    }
+}
+
+/// Number of header lines searched for the `@generated` marker.
+const GENERATED_MARKER_LINES: usize = 5;
+
+/// Whether the file declares itself as generated, as prost, bindgen and cargo do.
+pub fn has_generated_marker(path: &Path) -> bool {
+    match File::open(path) {
+        Ok(f) => BufReader::new(f)
+            .lines()
+            .take(GENERATED_MARKER_LINES)
+            .flatten()
+            .any(|l| l.contains("@generated")),
+        Err(_) => false,
+    }
+}
+
+/// Get the `.rs` files under `dir` which are not matched by `.gitignore`/`.ignore` files.
+fn get_not_ignored_files(dir: &Path) -> HashSet<PathBuf> {
+    WalkBuilder::new(dir)
+        .hidden(false)
+        .require_git(false)
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map_or(false, |t| t.is_file()))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// Get the `.rs` files under `dir`, skipping the dirs matched by `exclude`.
+fn get_rs_files(dir: &Path, root: &Path, exclude: &GlobSet) -> Vec<PathBuf> {
+    WalkDir::new(dir)
         .into_iter()
-        .filter_map(Result::ok)
-        .filter(|dir| op(dir, name, exclude.clone()))
-    {
-        dir_result.insert(e.path().to_path_buf(), e);
+        .filter_entry(|e| e.file_type().is_file() || !glob_match(exclude, root, e.path()))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.path().extension().map_or(false, |x| x == "rs"))
+        .map(|e| e.path().to_path_buf())
+        .collect()
+}
+
+/// The string of a `#[path = "..."]` attribute.
+fn path_attr(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .find(|a| a.path.is_ident("path"))
+        .and_then(|a| match a.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(s),
+                ..
+            })) => Some(s.value()),
+            _ => None,
+        })
+}
+
+/// Walks the module tree of a target from its root file.
+struct ModuleWalker<'a> {
+    package_dir: &'a Path,
+    root: &'a Path,
+    exclude: &'a GlobSet,
+    /// The files found, with their module path.
+    files: BTreeMap<PathBuf, Vec<String>>,
+}
+
+impl ModuleWalker<'_> {
+    /// Add the file of the module and follow its `mod foo;` declarations, `dir` holding its
+    /// submodules. A file which doesn't parse adds the `.rs` files under `dir` instead, with
+    /// the module paths of their location, unless that is the package dir, e.g. of `build.rs`.
+    fn file(&mut self, file: &Path, dir: &Path, module: Vec<String>) {
+        if self.files.contains_key(file) {
+            return;
+        }
+        self.files.insert(file.to_path_buf(), module.clone());
+        let parsed = std::fs::read_to_string(file)
+            .map_err(anyhow::Error::from)
+            .and_then(|s| syn::parse_file(&s).map_err(anyhow::Error::from));
+        match parsed {
+            Ok(f) => self.items(&f.items, file.parent().unwrap_or(dir), dir, &module),
+            Err(e) => {
+                log::debug!("Can't follow the modules of {:?}: {:?}", file, e);
+                if dir == self.package_dir {
+                    return;
+                }
+                for f in get_rs_files(dir, self.root, self.exclude) {
+                    let mut path = module.clone();
+                    path.extend(module_of_path(f.strip_prefix(dir).unwrap_or(&f)));
+                    self.files.entry(f).or_insert(path);
+                }
+            }
+        }
     }
-    Ok(dir_result)
+
+    /// Follow the module declarations of the items. `#[path]` is relative to `path_dir`.
+    fn items(&mut self, items: &[syn::Item], path_dir: &Path, dir: &Path, module: &[String]) {
+        for item in items {
+            let m = match item {
+                syn::Item::Mod(m) => m,
+                _ => continue,
+            };
+            let name = m.ident.to_string();
+            let path = path_attr(&m.attrs);
+            let mut module = module.to_vec();
+            module.push(name.clone());
+            if let Some((_, items)) = &m.content {
+                let dir = dir.join(path.unwrap_or(name));
+                self.items(items, &dir, &dir, &module);
+                continue;
+            }
+            // A `#[path]` file and a `mod.rs` hold their submodules in their own dir.
+            let (child, own_dir) = match path {
+                Some(p) => {
+                    let p = path_dir.join(p);
+                    (p.canonicalize().unwrap_or(p), true)
+                }
+                None => {
+                    let flat = dir.join(format!("{}.rs", name));
+                    if flat.is_file() {
+                        (flat, false)
+                    } else {
+                        (dir.join(&name).join("mod.rs"), true)
+                    }
+                }
+            };
+            if !child.is_file() {
+                continue;
+            }
+            let child_dir = match child.parent() {
+                Some(d) if own_dir => d.to_path_buf(),
+                _ => dir.join(&name),
+            };
+            self.file(&child, &child_dir, module);
+        }
+    }
+}
+
+/// Module path of a file from its path relative to the module dir, e.g. `parser::lexer` of
+/// `parser/lexer.rs` and `parser` of `parser/mod.rs`.
+pub fn module_of_path(rel: &Path) -> Vec<String> {
+    let mut module: Vec<String> = rel
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if module.last().map_or(false, |m| m == "mod") {
+        module.pop();
+    }
+    module
 }
 
+/// Get the source files of the workspace targets selected by `cfg`, only of `package` if given.
+/// The files of a target are its root file and the files of its module tree, so the sources
+/// of the targets left out, e.g. `src/main.rs` and `src/bin/` with `bin = false`, are not
+/// taken along with the library in the same dir.
+pub fn get_target_files(
+    ctx: &context::ProjectContext,
+    cfg: &config::SourceConfig,
+    package: Option<&str>,
+    root: &Path,
+    exclude: &GlobSet,
+) -> Result<SourceFiles> {
+    let generated = make_globset(&cfg.generated_files.clone().unwrap_or_default())?;
+    let respect_ignore = cfg.respect_ignore.unwrap_or(true);
+    let generated_marker = cfg.generated_marker.unwrap_or(true);
+
+    let mut candidates: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut modules = BTreeMap::new();
+    // The files of the package dirs not matched by the ignore files.
+    let mut ignore_roots: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut not_ignored: HashSet<PathBuf> = HashSet::new();
+    for t in ctx.targets.iter() {
+        if package.map_or(false, |p| p != t.package) {
+            continue;
+        }
+        if !t.target.kind.iter().any(|k| cfg.includes(k)) {
+            continue;
+        }
+        let src_path = t.src_path();
+        let mut walker = ModuleWalker {
+            package_dir: &t.package_dir,
+            root,
+            exclude,
+            files: BTreeMap::new(),
+        };
+        walker.file(
+            &src_path,
+            src_path.parent().unwrap_or(t.package_dir.as_path()),
+            vec![],
+        );
+        let krate = t.target.name.replace('-', "_");
+        for (f, module) in walker.files {
+            candidates.insert(f.clone());
+            modules.entry(f).or_insert_with(|| (krate.clone(), module));
+        }
+        if respect_ignore && ignore_roots.insert(t.package_dir.clone()) {
+            not_ignored.extend(get_not_ignored_files(&t.package_dir));
+        }
+    }
+    let ignored =
+        |f: &Path| ignore_roots.iter().any(|d| f.starts_with(d)) && !not_ignored.contains(f);
+
+    let mut source_files = SourceFiles {
+        modules,
+        ..Default::default()
+    };
+    for f in candidates {
+        let reason = if glob_match(exclude, root, &f) {
+            Some(ExcludeReason::ExcludePattern)
+        } else if ignored(&f) {
+            Some(ExcludeReason::IgnoreFile)
+        } else if glob_match(&generated, root, &f) {
+            Some(ExcludeReason::GeneratedPattern)
+        } else if generated_marker && has_generated_marker(&f) {
+            Some(ExcludeReason::GeneratedMarker)
+        } else {
+            None
+        };
+        match reason {
+            Some(r) => {
+                source_files.excluded.insert(f, r);
+            }
+            None => {
+                source_files.files.insert(f);
+            }
+        }
+    }
+    log::info!(
+        "Found {} source files, excluded {}.",
+        source_files.files.len(),
+        source_files.excluded.len()
+    );
+    Ok(source_files)
+}
+
+/// Install the Ctrl-C handler.
+/// Running commands are killed and pending check items are skipped once it fires.
+pub fn install_cancel_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        log::warn!("Received Ctrl-C, cancelling the evaluation.");
+        CANCELLED.store(true, Ordering::SeqCst);
+    })
+    .context("Failed to install the Ctrl-C handler")
+}
+
+/// Whether the evaluation has been cancelled by the user, or the check item running on the
+/// thread has been abandoned.
+pub fn is_cancelled() -> bool {
+    CancelToken::current().is_cancelled()
+}
+
+/// Cancellation signal of a check item, set when the item is abandoned on timeout or Ctrl-C,
+/// so its thread and workers stop at the next poll instead of running on detached.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the item has been abandoned or the evaluation cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        CANCELLED.load(Ordering::SeqCst) || self.0.load(Ordering::SeqCst)
+    }
+
+    /// Make the token the one of the current thread, seen by `is_cancelled`.
+    pub fn enter(&self) {
+        CURRENT_TOKEN.with(|t| *t.borrow_mut() = self.clone());
+    }
+
+    /// The token of the current thread. Worker threads of an item take it along.
+    pub fn current() -> CancelToken {
+        CURRENT_TOKEN.with(|t| t.borrow().clone())
+    }
+}
+
+/// Error of an external command or check item which did not run to completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterruptError {
+    TimedOut(Duration),
+    Cancelled,
+}
+
+impl std::fmt::Display for InterruptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterruptError::TimedOut(d) => write!(f, "timed out after {}s", d.as_secs()),
+            InterruptError::Cancelled => write!(f, "cancelled by user"),
+        }
+    }
+}
+
+impl std::error::Error for InterruptError {}
+
 /// Standard output type.
 #[derive(Debug)]
 pub enum StdOutput {
@@ -44,27 +372,158 @@ pub enum StdOutput {
 }
 
 /// Run the cargo subcommand.
+/// The child process is killed when `timeout` elapses or the evaluation is cancelled.
 pub fn cargo(
     sub_command: &str,
     manifest_file: &PathBuf,
     result_file: &PathBuf,
     std_info: StdOutput,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    cargo_with_args(
+        sub_command,
+        &[],
+        manifest_file,
+        result_file,
+        std_info,
+        timeout,
+    )
+}
+
+/// Kill the child and the processes it started, e.g. the `rustc` run by `cargo`, which would
+/// otherwise keep running and hold the target dir lock.
+fn kill_tree(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: `killpg` only sends a signal, to the group created with the child.
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+    }
+    #[cfg(windows)]
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Run the cargo subcommand with extra arguments, e.g. `--message-format=json`.
+pub fn cargo_with_args(
+    sub_command: &str,
+    args: &[&str],
+    manifest_file: &Path,
+    result_file: &Path,
+    std_info: StdOutput,
+    timeout: Option<Duration>,
 ) -> Result<()> {
+    let f = File::create(result_file)?;
     let mut cmd = Command::new("cargo");
     cmd.arg(sub_command)
+        .args(args)
         .arg("--manifest-path")
-        .arg(manifest_file);
-    let output = cmd
-        .output()
+        .arg(manifest_file)
+        .stdin(Stdio::null());
+    // Redirect the output to the result file directly, so a chatty child can't block on a full pipe.
+    match std_info {
+        StdOutput::Out => cmd.stdout(f).stderr(Stdio::null()),
+        StdOutput::Err => cmd.stdout(Stdio::null()).stderr(f),
+    };
+    // In its own process group, so the compilers started by cargo can be killed with it.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let mut child = cmd
+        .spawn()
         .with_context(|| format!("Failed to run command: {:?}", cmd))?;
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        let interrupt = if is_cancelled() {
+            Some(InterruptError::Cancelled)
+        } else {
+            timeout
+                .filter(|t| start.elapsed() >= *t)
+                .map(InterruptError::TimedOut)
+        };
+        if let Some(e) = interrupt {
+            log::error!(
+                "Run the cargo subcommand : {:?} {}, killing it.",
+                sub_command,
+                e
+            );
+            kill_tree(&mut child);
+            return Err(e.into());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
     // `cargo clippy` will fail when has errors.
-    if !output.status.success() {
+    if !status.success() {
         log::error!("Run the cargo subcommand : {:?} failed.", sub_command);
     }
-    let mut f = File::create(result_file)?;
-    match std_info {
-        StdOutput::Out => f.write_all(&output.stdout)?,
-        StdOutput::Err => f.write_all(&output.stderr)?,
-    };
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |file: &str, text: &str| {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        };
+        write(
+            "src/lib.rs",
+            "mod parser;\n#[path = \"gen/out.rs\"]\nmod tables;\nmod inline { mod deep; }\n",
+        );
+        write("src/parser/mod.rs", "mod lexer;\n");
+        write("src/parser/lexer.rs", "");
+        write("src/gen/out.rs", "");
+        write("src/inline/deep.rs", "");
+        write("src/main.rs", "fn main() {}\n");
+
+        let src = dir.path().join("src");
+        let exclude = GlobSet::empty();
+        let mut walker = ModuleWalker {
+            package_dir: dir.path(),
+            root: dir.path(),
+            exclude: &exclude,
+            files: BTreeMap::new(),
+        };
+        walker.file(&src.join("lib.rs"), &src, vec![]);
+        let mut modules: Vec<(PathBuf, String)> = walker
+            .files
+            .into_iter()
+            .map(|(f, m)| {
+                let f = f.canonicalize().unwrap();
+                (
+                    f.strip_prefix(src.canonicalize().unwrap())
+                        .unwrap()
+                        .to_path_buf(),
+                    m.join("::"),
+                )
+            })
+            .collect();
+        let expected = [
+            ("gen/out.rs", "tables"),
+            ("inline/deep.rs", "inline::deep"),
+            ("lib.rs", ""),
+            ("parser/lexer.rs", "parser::lexer"),
+            ("parser/mod.rs", "parser"),
+        ];
+        modules.sort();
+        assert_eq!(
+            modules,
+            expected
+                .iter()
+                .map(|(f, m)| (PathBuf::from(f), m.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+}